@@ -1,66 +1,323 @@
-use crate::{database, types};
-
-pub fn auto_tag_project(proj: &types::DataType) -> () {
-    let proj = proj.project().expect("Expected a project");
-    let mut tags = vec![];
-    tags.push(check_git(proj));
-    tags.push(check_rust(proj));
-    tags.push(check_svelte(proj));
-    // TODO: Add more checks here, e.g. for Python, JS, HTML, Go, etc.
-    // TODO: Feature to add collection of file extensions with corresponding tags e.g. .js -> JS, .py -> Python
-    tags.iter().for_each(|tag| match tag {
-        Some(tag) => {
-            let _ = database::add_tag(&types::DataType::Project(proj.clone()), tag.clone(), true);
+//! Automatically tag projects based on the files they contain.
+//!
+//! Tags are found by the detectors in `detectors`, most of which are rules. The rules are read
+//! from `default_rules.toml`, shipped with the binary, and the user's `rules.toml` in the config
+//! folder. A user rule with the same name as a default rule replaces it, and setting
+//! `defaults = false` in the user file disables the shipped rules altogether.
+
+use crate::error::{Error, Result};
+use crate::{config, database, detectors, git, languages, types};
+use clap::{arg, Command};
+use globset::Glob;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+
+const DEFAULT_RULES: &str = include_str!("default_rules.toml");
+
+/// How many folders deep to search for files when counting file extensions
+const MAX_DEPTH: usize = 4;
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_min_files() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default = "default_true")]
+    defaults: bool,
+    #[serde(default)]
+    rule: Vec<Rule>,
+    #[serde(default)]
+    languages: languages::Settings,
+}
+
+impl Default for RulesFile {
+    fn default() -> Self {
+        RulesFile {
+            defaults: true,
+            rule: Vec::new(),
+            languages: Default::default(),
         }
-        None => (),
-    });
-    ()
+    }
 }
 
-fn check_git(proj: &types::Project) -> Option<types::Tag> {
-    let git_tag = types::Tag {
-        name: "git".to_string(),
-    };
-    if proj.tags.contains(&git_tag) {
-        return None;
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Defaults to the first tag
+    name: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    files: Vec<String>,
+    #[serde(default)]
+    dirs: Vec<String>,
+    #[serde(default)]
+    globs: Vec<String>,
+    extension: Option<String>,
+    #[serde(default = "default_min_files")]
+    min_files: usize,
+}
+
+impl Rule {
+    pub fn name(&self) -> &str {
+        match &self.name {
+            Some(name) => name,
+            None => self.tags.first().map(|t| t.as_str()).unwrap_or_default(),
+        }
     }
-    //check if .git directory exists
-    let git_path = std::path::Path::new(&proj.path).join(".git");
-    if git_path.exists() {
-        return Some(git_tag);
-    } else {
-        return None;
+
+    /// The first marker of this rule that is found in the project folder, if any
+    pub fn evidence(&self, ctx: &detectors::Context) -> Option<String> {
+        let dir = ctx.dir;
+        if let Some(file) = self.files.iter().find(|file| dir.join(file).exists()) {
+            return Some(file.clone());
+        }
+        if let Some(sub) = self.dirs.iter().find(|sub| dir.join(sub).is_dir()) {
+            return Some(format!("{}/", sub));
+        }
+        if !self.globs.is_empty() {
+            let matchers = self
+                .globs
+                .iter()
+                .filter_map(|glob| match Glob::new(glob) {
+                    Ok(glob) => Some(glob.compile_matcher()),
+                    Err(e) => {
+                        eprintln!("Invalid glob in rule '{}': {}", self.name(), e);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            let found = std::fs::read_dir(dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .find(|entry| matchers.iter().any(|m| m.is_match(entry.file_name())));
+            if let Some(entry) = found {
+                return Some(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        if let Some(extension) = &self.extension {
+            let count = ctx.census.extensions.get(extension).copied().unwrap_or(0);
+            if count >= self.min_files {
+                return Some(format!("{} .{} files", count, extension));
+            }
+        }
+        None
     }
 }
 
-fn check_rust(proj: &types::Project) -> Option<types::Tag> {
-    let rust_tag = types::Tag {
-        name: "rust".to_string(),
-    };
-    if proj.tags.contains(&rust_tag) {
-        return None;
+impl detectors::Detector for Rule {
+    fn name(&self) -> &str {
+        Rule::name(self)
     }
-    //check if Cargo.toml exists
-    let cargo_path = std::path::Path::new(&proj.path).join("Cargo.toml");
-    if cargo_path.exists() {
-        return Some(rust_tag);
-    } else {
-        return None;
+
+    fn description(&self) -> String {
+        format!("rule: {}", self.tags.join(", "))
     }
+
+    fn evaluate(&self, ctx: &detectors::Context) -> Vec<types::Detection> {
+        match self.evidence(ctx) {
+            Some(evidence) => self
+                .tags
+                .iter()
+                .map(|tag| types::Detection {
+                    tag: types::Tag {
+                        name: tag.to_lowercase(),
+                    },
+                    evidence: evidence.clone(),
+                })
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+fn parse_rules(contents: &str) -> std::result::Result<RulesFile, toml::de::Error> {
+    toml::from_str(contents)
 }
 
-fn check_svelte(proj: &types::Project) -> Option<types::Tag> {
-    let svelte_tag = types::Tag {
-        name: "svelte".to_string(),
+/// The user's rules file. An invalid file is an error rather than ignored, as retagging without
+/// the user's rules would remove the tags they found
+fn load_user_file() -> Result<RulesFile> {
+    let path = config::rules_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            parse_rules(&contents).map_err(|source| Error::InvalidFile { path, source })
+        }
+        Err(_) => Ok(Default::default()),
+    }
+}
+
+/// Everything the user's rules file configures, read in one go
+pub struct Rules {
+    pub rules: Vec<Rule>,
+    pub languages: languages::Settings,
+}
+
+/// The shipped default rules combined with the user's rules file
+pub fn load_rules() -> Result<Rules> {
+    let defaults = parse_rules(DEFAULT_RULES).expect("The default rules should be valid");
+    let user = load_user_file()?;
+
+    let mut rules = Vec::new();
+    if user.defaults {
+        rules.extend(
+            defaults
+                .rule
+                .into_iter()
+                .filter(|rule| !user.rule.iter().any(|r| r.name() == rule.name())),
+        );
+    }
+    rules.extend(user.rule);
+    Ok(Rules {
+        rules,
+        languages: user.languages,
+    })
+}
+
+/// Run all enabled detectors on a project, adding newly detected tags and removing auto-detected
+/// tags whose evidence is gone. Returns which tags changed
+pub fn auto_tag_project(data: &types::DataType) -> Result<types::TagDiff> {
+    auto_tag_with(&detectors::Registry::load()?, data)
+}
+
+/// Same as `auto_tag_project`, reusing an already loaded registry when tagging many projects
+pub fn auto_tag_with(
+    registry: &detectors::Registry,
+    data: &types::DataType,
+) -> Result<types::TagDiff> {
+    let proj = data.project().expect("Expected a project");
+    let dir = Path::new(&proj.path);
+    let store = database::store()?;
+    // NOTE: Stored even when their detectors are disabled, `project show` lists them either way
+    let remotes = git::remotes(dir);
+    store.set_remotes(data, &remotes)?;
+    let census = languages::census(dir, MAX_DEPTH);
+    store.set_languages(data, &census.languages)?;
+    let ctx = detectors::Context {
+        dir,
+        remotes: &remotes,
+        census: &census,
     };
-    if proj.tags.contains(&svelte_tag) {
-        return None;
+    let before = store.get_direct_tags(data)?;
+
+    let mut detected_tags = HashSet::new();
+    let mut added = Vec::new();
+    for detector in registry.enabled() {
+        let source = types::TagSource::Auto(detector.name().to_string());
+        for detection in detector.evaluate(&ctx) {
+            let tag = types::Tag {
+                name: detection.tag.name.to_lowercase(),
+            };
+            store.add_tag(data, tag.clone(), true, &source)?;
+            // NOTE: Only report the first detector that found a tag
+            if detected_tags.insert(tag.clone()) && !before.contains(&tag) {
+                added.push(types::Detection {
+                    tag,
+                    evidence: detection.evidence,
+                });
+            }
+        }
+    }
+    let removed = store.remove_stale_tags(data, &detected_tags, types::TagSource::is_auto)?;
+
+    added.sort_by(|a, b| a.tag.name.cmp(&b.tag.name));
+    Ok(types::TagDiff { added, removed })
+}
+
+pub fn command() -> Command {
+    Command::new("auto-tag")
+        .about("Manage the detectors used to tag projects automatically")
+        .arg_required_else_help(true)
+        .subcommand(Command::new("list").about("List all detectors"))
+        .subcommand(
+            Command::new("enable")
+                .about("Enable detectors")
+                .arg(arg!(<NAME>).required(true).num_args(1..)),
+        )
+        .subcommand(
+            Command::new("disable")
+                .about("Disable detectors")
+                .arg(arg!(<NAME>).required(true).num_args(1..)),
+        )
+}
+
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    match args.subcommand() {
+        Some(("list", _)) => list(),
+        Some(("enable", args)) => set_enabled(
+            args.get_many::<String>("NAME")
+                .unwrap_or_default()
+                .map(|v| v.as_str())
+                .collect(),
+            true,
+        ),
+        Some(("disable", args)) => set_enabled(
+            args.get_many::<String>("NAME")
+                .unwrap_or_default()
+                .map(|v| v.as_str())
+                .collect(),
+            false,
+        ),
+        _ => panic!("Should be handled by clap itself"),
+    }
+}
+
+fn list() -> Result<()> {
+    let registry = detectors::Registry::load()?;
+    registry.all().for_each(|detector| {
+        let state = if registry.is_enabled(detector.name()) {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        println!(
+            "{:<20} {:<9} {}",
+            detector.name(),
+            state,
+            detector.description()
+        );
+    });
+    Ok(())
+}
+
+fn set_enabled(names: Vec<&str>, enabled: bool) -> Result<()> {
+    let registry = detectors::Registry::load()?;
+    // NOTE: Check all names first, so a typo doesn't leave only some detectors changed
+    if let Some(name) = names.iter().find(|name| !registry.contains(name)) {
+        return Err(Error::DetectorNotFound(name.to_string()));
     }
-    //check if svelte.config.js exists
-    let svelte_path = std::path::Path::new(&proj.path).join("svelte.config.js");
-    if svelte_path.exists() {
-        return Some(svelte_tag);
-    } else {
-        return None;
+    for name in names {
+        database::store()?.set_detector_enabled(name, enabled)?;
+        if enabled {
+            println!("Detector enabled: {}", name);
+        } else {
+            println!("Detector disabled: {}", name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_rules_parse() {
+        assert!(parse_rules(DEFAULT_RULES).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(parse_rules("default = false").is_err());
+        assert!(parse_rules("[[rules]]\ntags = [\"notes\"]").is_err());
+        assert!(parse_rules("[[rule]]\ntags = [\"notes\"]\nfile = [\"NOTES.md\"]").is_err());
+        assert!(parse_rules("defaults = false\n[[rule]]\ntags = [\"notes\"]").is_ok());
     }
 }
@@ -1,9 +1,15 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use clap::{arg, value_parser, Command};
-use fzf_wrapped::Fzf;
+use globset::{Glob, GlobSet, GlobSetBuilder};
 
+use crate::completions;
+use crate::config;
 use crate::database;
+use crate::error::{Error, Result};
+use crate::folder_file;
+use crate::output;
+use crate::picker;
 use crate::projects;
 use crate::tags;
 use crate::types;
@@ -12,19 +18,51 @@ pub fn command() -> Command {
     Command::new("collection")
         .about("Manage collections / folders with projects")
         .arg_required_else_help(true)
-        .subcommand(Command::new("list").about("List all collections"))
         .subcommand(
-            Command::new("add").about("Add a new collection").arg(
-                arg!(<PATH>)
-                    .required(true)
-                    .value_parser(value_parser!(PathBuf)),
-            ),
+            Command::new("list").about("List all collections"),
+        )
+        .subcommand(
+            Command::new("add")
+                .about("Add a new collection")
+                .arg(
+                    arg!(<PATH>)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    arg!(-d --depth <N>)
+                        .help("Levels below the collection where projects are, e.g. 3 for src/github.com/org/repo")
+                        .value_parser(value_parser!(u64).range(1..)),
+                )
+                .arg(
+                    arg!(-r --"repos-only")
+                        .help("Only add project roots (.git, Cargo.toml, ...) up to --depth levels deep, 4 by default"),
+                )
+                .arg(
+                    arg!(-i --ignore <GLOB>)
+                        .help("Skip folders matching the glob, e.g. 'archive' or 'tmp*'. Can be repeated")
+                        .action(clap::ArgAction::Append),
+                )
+                .arg(arg!(--"include-hidden" "Also add folders starting with a '.'")),
+        )
+        .subcommand(
+            Command::new("ignore")
+                .about("Show, add or remove the ignore patterns of a collection")
+                .arg(
+                    arg!(<PATH>)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::collection_paths()),
+                )
+                .arg(arg!([GLOB]).num_args(1..))
+                .arg(arg!(--remove "Remove the patterns instead of adding them").requires("GLOB")),
         )
         .subcommand(
             Command::new("del").about("Delete a collection").arg(
                 arg!(<PATH>)
                     .required(true)
-                    .value_parser(value_parser!(PathBuf)),
+                    .value_parser(value_parser!(PathBuf))
+                    .add(completions::collection_paths()),
             ),
         )
         .subcommand(
@@ -33,19 +71,82 @@ pub fn command() -> Command {
                 .arg(
                     arg!(<PATH>)
                         .required(true)
-                        .value_parser(value_parser!(PathBuf)),
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::collection_paths()),
                 )
                 .arg(arg!(-f --force "Add tags if they don't exist'"))
-                .arg(arg!(<TAG>).required(false).num_args(1..)),
+                .arg(
+                    arg!(<TAG>)
+                        .required(false)
+                        .num_args(1..)
+                        .add(completions::tag_names()),
+                ),
+        )
+        .subcommand(
+            Command::new("sync")
+                .about("Add new subfolders of a collection and report removed ones")
+                .arg(
+                    arg!([PATH])
+                        .required_unless_present("all")
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::collection_paths()),
+                )
+                .arg(arg!(-a --all "Sync all collections").conflicts_with("PATH"))
+                .arg(arg!(-n --"dry-run" "Only show what would change"))
+                .arg(arg!(--prune "Delete projects whose folder is gone")),
+        )
+        .subcommand(
+            Command::new("untag")
+                .about("Remove tags from a collection")
+                .arg(
+                    arg!(<PATH>)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::collection_paths()),
+                )
+                .arg(
+                    arg!([TAG])
+                        .num_args(1..)
+                        .add(completions::tag_names()),
+                ),
+        )
+        .subcommand(
+            Command::new("goto").about("Go to a collection").arg(
+                arg!(--"print-path" "Only print the selected path, for use in shell wrappers"),
+            ),
         )
-        .subcommand(Command::new("goto").about("Go to a collection"))
 }
 
-pub fn run(args: &clap::ArgMatches) {
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
     match args.subcommand() {
-        Some(("list", _)) => list(),
-        Some(("goto", args)) => goto(),
-        Some(("add", args)) => add(args.get_one::<PathBuf>("PATH").unwrap().clone(), true),
+        Some(("list", args)) => list(output::Format::from_args(args)),
+        Some(("goto", args)) => goto(args.get_flag("print-path")),
+        Some(("add", args)) => {
+            let repos_only = args.get_flag("repos-only");
+            let default_depth = if repos_only { 4 } else { 1 };
+            let scan = types::ScanSettings {
+                depth: args
+                    .get_one::<u64>("depth")
+                    .map(|&depth| depth as usize)
+                    .unwrap_or(default_depth),
+                repos_only,
+                include_hidden: args.get_flag("include-hidden"),
+                ignore: args
+                    .get_many::<String>("ignore")
+                    .unwrap_or_default()
+                    .cloned()
+                    .collect(),
+            };
+            add(args.get_one::<PathBuf>("PATH").unwrap().clone(), scan, true)
+        }
+        Some(("ignore", args)) => ignore(
+            args.get_one::<PathBuf>("PATH").unwrap().clone(),
+            args.get_many::<String>("GLOB")
+                .unwrap_or_default()
+                .cloned()
+                .collect(),
+            args.get_flag("remove"),
+        ),
         Some(("del", args)) => del(args.get_one::<PathBuf>("PATH").unwrap().clone()),
         Some(("tag", args)) => add_tag(
             args.get_one::<PathBuf>("PATH").unwrap().clone(),
@@ -57,102 +158,373 @@ pub fn run(args: &clap::ArgMatches) {
                 })
                 .collect(),
         ),
+        Some(("sync", args)) => sync(
+            args.get_one::<PathBuf>("PATH").cloned(),
+            args.get_flag("dry-run"),
+            args.get_flag("prune"),
+        ),
+        Some(("untag", args)) => remove_tag(
+            args.get_one::<PathBuf>("PATH").unwrap().clone(),
+            args.get_many::<String>("TAG")
+                .unwrap_or_default()
+                .map(|name| types::Tag {
+                    name: name.to_string(),
+                })
+                .collect(),
+        ),
         _ => panic!("Should be handled by clap itself"),
     }
 }
 
-fn path_to_collection(path: &PathBuf) -> types::DataType {
-    let full_path = path.canonicalize().unwrap();
+fn path_to_collection(path: &Path) -> Result<types::DataType> {
     let collection = types::Collection {
-        path: full_path.to_str().unwrap().to_string(),
+        id: None,
+        path: projects::full_path(path)?,
+        scan: Default::default(),
         tags: Default::default(),
+        tag_links: Vec::new(),
     };
-    types::DataType::Collection(collection)
+    Ok(types::DataType::Collection(collection))
 }
 
-fn list() {
-    let collections = database::list_collections().unwrap();
-    collections
-        .iter()
-        .for_each(|collection| println!("{}", collection));
+fn list(format: output::Format) -> Result<()> {
+    let collections = database::store()?.list_collections()?;
+    output::print(&collections, format);
+    Ok(())
 }
 
-fn goto() {
-    let collections = database::list_collections().unwrap();
-    let strings: Vec<String> = collections.iter().map(|proj| format!("{}", proj)).collect();
-    let mut fzf = Fzf::default();
-    fzf.run().expect("Failed to start fzf");
-    fzf.add_items(&strings).expect("Failed to add items");
-    let users_selection = fzf.output().expect("Failed to get the user's output");
-    let index = strings.iter().position(|x| x == &users_selection).unwrap();
+fn goto(print_path: bool) -> Result<()> {
+    let collections = database::store()?.list_collections()?;
+    let lines: Vec<String> = collections.iter().map(|c| format!("{}", c)).collect();
+    let index = picker::pick("Collection", &lines)?.ok_or(Error::Cancelled)?;
     let proj = &collections[index];
 
     println!("{}", proj.path);
+    if !print_path {
+        // NOTE: A child process can't change the directory of its parent shell
+        eprintln!(
+            "Use the `ff` function from `shell-init` to change into the collection directory"
+        );
+    }
+    Ok(())
 }
 
-fn add(path: PathBuf, ask_tags: bool) {
-    let collection = path_to_collection(&path);
-    let res = database::add(&collection);
-    match res {
-        Ok(_) => println!("Collection added"),
-        Err(_e) => {
-            eprintln!("The collection already exists");
-            return;
-        }
+fn add(path: PathBuf, scan_settings: types::ScanSettings, ask_tags: bool) -> Result<()> {
+    check_patterns(&scan_settings.ignore)?;
+    projects::existing_path(&path)?;
+    let mut collection = path_to_collection(&path)?;
+    if let types::DataType::Collection(c) = &mut collection {
+        c.scan = scan_settings.clone();
     }
-    add_tag(path.clone(), true, tags::select_tags(true));
+    database::store()?.add(&collection)?;
+    println!("Collection added");
+    folder_file::import(&collection)?;
 
     // NOTE: Automatically add all subdirectories as projects
-    let dir = std::fs::read_dir(&path).unwrap();
-    let subdirs = dir
-        .filter_map(|entry| {
-            let entry = entry.unwrap();
-            if entry.file_type().unwrap().is_dir() {
-                Some(entry.path())
+    for subdir in scan(&path, &scan_settings)? {
+        add_project(&subdir, &collection)?;
+    }
+
+    // NOTE: Asked last, so the collection is complete even if the prompt fails
+    if ask_tags {
+        let tags = tags::select_tags(true)?;
+        if !tags.is_empty() {
+            add_tag(path, true, tags)?;
+        }
+    }
+    Ok(())
+}
+
+/// Files and folders that mark the root of a project when scanning with `repos_only`
+const ROOT_MARKERS: [&str; 12] = [
+    ".git",
+    "Cargo.toml",
+    "package.json",
+    "go.mod",
+    "pyproject.toml",
+    "setup.py",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "Makefile",
+    "flake.nix",
+    "mix.exs",
+];
+
+fn is_project_root(path: &Path) -> bool {
+    ROOT_MARKERS.iter().any(|marker| path.join(marker).exists())
+}
+
+fn subdirs(path: &Path) -> Vec<PathBuf> {
+    let Ok(dir) = std::fs::read_dir(path) else {
+        return Vec::new();
+    };
+    let mut subdirs = dir
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    subdirs.sort();
+    subdirs
+}
+
+/// File in the collection folder with extra ignore patterns, one per line
+const IGNORE_FILE: &str = ".ffignore";
+
+/// The collection's own ignore patterns, the global ones from the config file and the ones in
+/// the collection's `.ffignore`
+fn ignore_patterns(path: &Path, settings: &types::ScanSettings) -> Result<Vec<String>> {
+    let mut patterns = settings.ignore.clone();
+    patterns.extend(config::load()?.ignore);
+    if let Ok(contents) = std::fs::read_to_string(path.join(IGNORE_FILE)) {
+        patterns.extend(
+            contents
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.trim_end_matches('/').to_string()),
+        );
+    }
+    Ok(patterns)
+}
+
+/// Refuse invalid globs before storing them, `ignore_matcher` would only warn on every scan
+fn check_patterns(patterns: &[String]) -> Result<()> {
+    for pattern in patterns {
+        Glob::new(pattern).map_err(|source| Error::InvalidPattern {
+            pattern: pattern.clone(),
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+fn ignore_matcher(patterns: &[String]) -> GlobSet {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        match Glob::new(pattern) {
+            Ok(glob) => {
+                builder.add(glob);
+            }
+            Err(e) => eprintln!("Invalid ignore pattern '{}': {}", pattern, e),
+        }
+    }
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("Invalid ignore patterns: {}", e);
+        GlobSet::empty()
+    })
+}
+
+/// Whether a folder below the collection should be skipped, checking both its name and its path
+/// relative to the collection
+fn is_ignored(root: &Path, dir: &Path, matcher: &GlobSet, include_hidden: bool) -> bool {
+    let Some(name) = dir.file_name() else {
+        return false;
+    };
+    if !include_hidden && name.to_string_lossy().starts_with('.') {
+        return true;
+    }
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    matcher.is_match(name) || matcher.is_match(relative)
+}
+
+/// Directories in the collection folder that should be projects
+fn scan(path: &Path, settings: &types::ScanSettings) -> Result<Vec<PathBuf>> {
+    let matcher = ignore_matcher(&ignore_patterns(path, settings)?);
+    let mut found = Vec::new();
+    let mut level = vec![path.to_path_buf()];
+    for depth in 1..=settings.depth {
+        let mut next = Vec::new();
+        for dir in subdirs_of(&level)
+            .into_iter()
+            .filter(|dir| !is_ignored(path, dir, &matcher, settings.include_hidden))
+        {
+            if settings.repos_only {
+                if is_project_root(&dir) {
+                    found.push(dir);
+                } else {
+                    next.push(dir);
+                }
+            } else if depth == settings.depth {
+                found.push(dir);
             } else {
-                None
+                next.push(dir);
             }
-        })
-        .collect::<Vec<_>>();
+        }
+        level = next;
+    }
+    Ok(found)
+}
+
+fn subdirs_of(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    dirs.iter().flat_map(|dir| subdirs(dir)).collect()
+}
+
+/// Add a folder of a collection as a project, or move an existing project into the collection
+fn add_project(path: &Path, collection: &types::DataType) -> Result<()> {
+    let proj = projects::from_path(path)?;
+    match projects::add(path.to_path_buf(), false) {
+        Err(e @ Error::AlreadyExists { .. }) => eprintln!("{}", e),
+        res => res?,
+    }
+    database::store()?.add_project_collection(&proj, collection)
+}
+
+fn sync(path: Option<PathBuf>, dry_run: bool, prune: bool) -> Result<()> {
+    let store = database::store()?;
+    let mut collections = store.list_collections()?;
+    if let Some(path) = path {
+        let full_path = projects::full_path(&path)?;
+        collections.retain(|c| c.path == full_path);
+        if collections.is_empty() {
+            return Err(Error::NotFound {
+                kind: types::EmptyDataType::Collection,
+                key: full_path,
+            });
+        }
+    }
+    let projects = store.list_projects()?;
 
-    subdirs.iter().for_each(|subdir| {
-        let proj = projects::from_path(&subdir);
-        projects::add(subdir.to_path_buf(), false);
-        database::add_project_collection(&proj, &collection);
-    });
-}
-
-fn del(path: PathBuf) {
-    let collection = path_to_collection(&path);
-    let res = database::delete(&collection);
-    match res {
-        Ok(_) => println!("Deleted"),
-        Err(_e) => {
-            eprintln!("Error deleting collection");
-            return;
+    for collection in collections {
+        let linked = projects
+            .iter()
+            .filter(|proj| {
+                proj.collection
+                    .as_ref()
+                    .is_some_and(|c| c.path == collection.path)
+            })
+            .collect::<Vec<_>>();
+        // NOTE: Without the folder, e.g. on an unmounted drive, every project would look removed
+        if !Path::new(&collection.path).is_dir() {
+            eprintln!(
+                "Skipping {}, the collection folder is missing",
+                collection.path
+            );
+            continue;
         }
+        let found = scan(Path::new(&collection.path), &collection.scan)?;
+        let found_paths = found
+            .iter()
+            .map(|subdir| projects::full_path(subdir))
+            .collect::<Result<Vec<_>>>()?;
+
+        println!("Syncing {}", collection.path);
+        let collection = types::DataType::Collection(collection);
+        if !dry_run {
+            print_imported(
+                &collection.collection().unwrap().path,
+                &folder_file::import(&collection)?,
+            );
+            for proj in linked
+                .iter()
+                .filter(|proj| found_paths.contains(&proj.path))
+            {
+                let diff = folder_file::import(&types::DataType::Project((*proj).clone()))?;
+                print_imported(&proj.path, &diff);
+            }
+        }
+        for subdir in found.iter().zip(&found_paths).filter_map(|(subdir, full)| {
+            (!linked.iter().any(|proj| &proj.path == full)).then_some(subdir)
+        }) {
+            if dry_run {
+                println!("Would add: {}", subdir.display());
+            } else {
+                add_project(subdir, &collection)?;
+            }
+        }
+
+        for proj in linked
+            .iter()
+            .filter(|proj| !found_paths.contains(&proj.path))
+        {
+            if !prune {
+                println!("Missing: {}", proj.path);
+            } else if dry_run {
+                println!("Would remove: {}", proj.path);
+            } else {
+                store.delete(&types::DataType::Project((*proj).clone()))?;
+                println!("Removed: {}", proj.path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Report the tags that changed by reading a `.folder_finder.toml` file
+fn print_imported(path: &str, diff: &types::TagDiff) {
+    diff.added
+        .iter()
+        .for_each(|d| println!("Tag added to {}: {}", path, d.tag.name));
+    diff.removed
+        .iter()
+        .for_each(|tag| println!("Tag removed from {}: {}", path, tag.name));
+}
+
+fn ignore(path: PathBuf, patterns: Vec<String>, remove: bool) -> Result<()> {
+    let collection = path_to_collection(&path)?;
+    if patterns.is_empty() {
+        let full_path = &collection.collection().unwrap().path;
+        let Some(c) = database::store()?
+            .list_collections()?
+            .into_iter()
+            .find(|c| &c.path == full_path)
+        else {
+            return Err(Error::NotFound {
+                kind: collection.kind(),
+                key: full_path.clone(),
+            });
+        };
+        c.scan.ignore.iter().for_each(|p| println!("{}", p));
+        return Ok(());
+    }
+    check_patterns(&patterns)?;
+    database::store()?.set_ignores(&collection, &patterns, !remove)?;
+    if remove {
+        println!("Ignore patterns removed");
+    } else {
+        println!("Ignore patterns added, run `collection sync --prune` to apply them");
     }
+    Ok(())
+}
+
+fn del(path: PathBuf) -> Result<()> {
+    let collection = path_to_collection(&path)?;
+    database::store()?.delete(&collection)?;
+    println!("Deleted");
+    Ok(())
 }
 
-fn add_tag(path: PathBuf, force: bool, tags: Vec<types::Tag>) {
+fn add_tag(path: PathBuf, force: bool, tags: Vec<types::Tag>) -> Result<()> {
+    let col = path_to_collection(&path)?;
     let tags = if tags.is_empty() {
-        tags::select_tags(force)
+        tags::select_tags(force)?
     } else {
         tags
     };
 
-    tags.iter().for_each(|tag| {
-        let col = path_to_collection(&path);
-        let res = database::add_tag(&col, tag.clone(), force);
-        match res {
-            Ok(_) => println!("Tag added: {}", tag.name),
-            Err(e) => {
-                match e {
-                    types::NotFoundError::Tag => eprintln!("Tag not found"),
-                    _ => eprintln!("Not currently in folder with collection"),
-                }
-                return;
-            }
+    for tag in tags {
+        database::store()?.add_tag(&col, tag.clone(), force, &types::TagSource::Manual)?;
+        println!("Tag added: {}", tag.name);
+    }
+    Ok(())
+}
+
+fn remove_tag(path: PathBuf, tags: Vec<types::Tag>) -> Result<()> {
+    let col = path_to_collection(&path)?;
+    let tags = if tags.is_empty() {
+        let current = database::store()?.get_direct_tags(&col)?;
+        if current.is_empty() {
+            println!("The collection has no tags to remove");
+            return Ok(());
         }
-    });
+        tags::select_from(current.into_iter().collect())?
+    } else {
+        tags
+    };
+
+    for tag in tags {
+        database::store()?.remove_tag(&col, tag.clone())?;
+        println!("Tag removed: {}", tag.name);
+    }
+    Ok(())
 }
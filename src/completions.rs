@@ -0,0 +1,137 @@
+//! Shell completions. The script printed by `completions` calls back into folder_finder on every
+//! <TAB>, so paths and tags are completed from the database.
+
+use crate::config;
+use crate::database::Store;
+use clap::{arg, Command};
+use clap_complete::engine::{
+    ArgValueCandidates, ArgValueCompleter, CompletionCandidate, PathCompleter, ValueCompleter,
+};
+use clap_complete::env::Shells;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+/// Environment variable the completion script sets when asking for completions
+pub const VAR: &str = "COMPLETE";
+
+pub fn command() -> Command {
+    Command::new("completions")
+        .about("Print a shell completion script")
+        .long_about(
+            "Print a shell completion script.\n\
+            Add the output to your shell config, e.g. `source <(folder_finder completions bash)`.\n\
+            Project paths, collection paths and tags are completed from the database. \
+            In bash and zsh the `ff` function from `shell-init` is completed as well.",
+        )
+        .arg(arg!(<SHELL>).required(true).value_parser([
+            "bash",
+            "zsh",
+            "fish",
+            "elvish",
+            "powershell",
+        ]))
+}
+
+pub fn run(args: &clap::ArgMatches) {
+    let shell = args.get_one::<String>("SHELL").unwrap();
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell)
+        .expect("Should be handled by clap itself");
+    let bin = env!("CARGO_PKG_NAME");
+    let mut out = std::io::stdout();
+    // NOTE: fish already completes `ff` through `--wraps`, the other shells have no `ff` function
+    let wrapper = matches!(shell.as_str(), "bash" | "zsh");
+    // NOTE: Nothing to report if stdout is closed early, e.g. when piped into `head`
+    let _ = completer
+        .write_registration(VAR, bin, bin, bin, &mut out)
+        .and_then(|()| {
+            if wrapper {
+                completer.write_registration(VAR, "ff", "ff", bin, &mut out)
+            } else {
+                Ok(())
+            }
+        });
+}
+
+/// Use the database chosen by `--db` or `--profile` on the line being completed. The completion
+/// engine runs before the arguments are parsed, so they are picked out by hand
+pub fn select_db(args: impl Iterator<Item = OsString>) {
+    let mut db = None;
+    let mut profile = None;
+    let mut args = args.skip_while(|arg| arg != "--");
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if let Some(value) = arg.strip_prefix("--db=") {
+            db = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            profile = Some(value.to_string());
+        } else if arg == "--db" {
+            db = args.next().map(PathBuf::from);
+        } else if arg == "--profile" {
+            profile = args
+                .next()
+                .map(|value| value.to_string_lossy().into_owned());
+        }
+    }
+    // NOTE: Completions never fail, with an invalid config only folders are completed
+    let _ = config::select_db(db.as_deref(), profile.as_deref());
+}
+
+/// Complete `current` from `paths`, or as a folder if none of them match so paths outside the
+/// database can still be typed
+fn complete_paths(paths: Vec<(String, String)>, current: &OsStr) -> Vec<CompletionCandidate> {
+    let current_str = current.to_string_lossy();
+    let candidates = paths
+        .into_iter()
+        .filter(|(path, _)| path.starts_with(current_str.as_ref()))
+        .map(|(path, help)| CompletionCandidate::new(path).help(Some(help.into())))
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        PathCompleter::dir().complete(current)
+    } else {
+        candidates
+    }
+}
+
+/// Paths of the projects in the database
+pub fn project_paths() -> ArgValueCompleter {
+    ArgValueCompleter::new(|current: &OsStr| {
+        // NOTE: Completions never create or migrate the database, without a usable one only
+        // folders are completed
+        let projects = Store::open_read_only()
+            .and_then(|store| store.list_projects())
+            .unwrap_or_default();
+        let paths = projects
+            .into_iter()
+            .map(|proj| (proj.path.clone(), proj.shown_name().to_string()))
+            .collect();
+        complete_paths(paths, current)
+    })
+}
+
+/// Paths of the collections in the database
+pub fn collection_paths() -> ArgValueCompleter {
+    ArgValueCompleter::new(|current: &OsStr| {
+        let collections = Store::open_read_only()
+            .and_then(|store| store.list_collections())
+            .unwrap_or_default();
+        let paths = collections
+            .into_iter()
+            .map(|c| (c.path, "collection".to_string()))
+            .collect();
+        complete_paths(paths, current)
+    })
+}
+
+/// Names of the tags in the database
+pub fn tag_names() -> ArgValueCandidates {
+    ArgValueCandidates::new(|| {
+        Store::open_read_only()
+            .and_then(|store| store.list_tags())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tag| CompletionCandidate::new(tag.name))
+            .collect()
+    })
+}
@@ -0,0 +1,122 @@
+//! Locations of the files folder_finder stores its data and configuration in
+
+use crate::error::{Error, Result};
+use crate::picker;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable with the path of the database, overridden by `--db`
+pub const DB_ENV: &str = "FOLDER_FINDER_DB";
+
+/// Database chosen with `--db` or `--profile`, see `select_db`
+static DB_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Settings from `config.toml` in the config folder
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Globs for folders that are never added as projects when scanning a collection
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Picker used by `goto`, `builtin` or `fzf`
+    #[serde(default)]
+    pub picker: picker::Backend,
+    /// Database used when neither `--db`, `--profile` nor `FOLDER_FINDER_DB` is given
+    pub db: Option<PathBuf>,
+    /// Named databases, selected with `--profile`
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Defaults to `<name>.sqlite` in the profiles folder of the data folder
+    pub db: Option<PathBuf>,
+}
+
+/// Load the config file, falling back to the defaults if it doesn't exist. An invalid file is an
+/// error rather than ignored, as it may select a different database
+pub fn load() -> Result<Config> {
+    let path = config_path()?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            toml::from_str(&contents).map_err(|source| Error::InvalidFile { path, source })
+        }
+        Err(_) => Ok(Default::default()),
+    }
+}
+
+/// Folder containing the configuration files
+pub fn config_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or(Error::NoHomeDir)?;
+    Ok(home.join(".config/folder_finder"))
+}
+
+/// Folder containing the databases, e.g. `~/.local/share/folder_finder` on Linux
+pub fn data_dir() -> Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or(Error::NoDataDir)?
+        .join("folder_finder"))
+}
+
+/// Expand a leading `~` to the home folder
+fn expand_home(path: &Path) -> Result<PathBuf> {
+    match path.strip_prefix("~") {
+        Ok(rest) => Ok(dirs::home_dir().ok_or(Error::NoHomeDir)?.join(rest)),
+        Err(_) => Ok(path.to_path_buf()),
+    }
+}
+
+/// Choose the database for this run from the `--db` and `--profile` arguments. Must be called
+/// before the database is opened
+pub fn select_db(db: Option<&Path>, profile: Option<&str>) -> Result<()> {
+    let path = match (db, profile) {
+        (Some(db), _) => expand_home(db)?,
+        (None, Some(name)) => match load()?.profiles.get(name).and_then(|p| p.db.clone()) {
+            Some(db) => expand_home(&db)?,
+            None => data_dir()?
+                .join("profiles")
+                .join(format!("{}.sqlite", name)),
+        },
+        (None, None) => return Ok(()),
+    };
+    DB_PATH
+        .set(path)
+        .expect("The database should only be selected once");
+    Ok(())
+}
+
+/// The database to use, in order: the one from `select_db`, `FOLDER_FINDER_DB`, `db` in the
+/// config file, then `db.sqlite` in the data folder. Databases created before the data folder was
+/// used are still found in the config folder
+pub fn db_path() -> Result<PathBuf> {
+    if let Some(path) = DB_PATH.get() {
+        return Ok(path.clone());
+    }
+    let path = if let Some(path) = std::env::var_os(DB_ENV).filter(|p| !p.is_empty()) {
+        expand_home(Path::new(&path))?
+    } else if let Some(path) = load()?.db {
+        expand_home(&path)?
+    } else {
+        let path = data_dir()?.join("db.sqlite");
+        let legacy = config_dir()?.join("db.sqlite");
+        if !path.exists() && legacy.exists() {
+            legacy
+        } else {
+            path
+        }
+    };
+    Ok(DB_PATH.get_or_init(|| path).clone())
+}
+
+pub fn config_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+/// User defined auto-tag rules, see `auto_tags`
+pub fn rules_path() -> Result<PathBuf> {
+    Ok(config_dir()?.join("rules.toml"))
+}
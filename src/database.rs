@@ -1,317 +1,1001 @@
-use rusqlite::named_params;
+use rusqlite::{params, OptionalExtension};
 
-use crate::types::{AlreadyExistsError, Collection, DataType, NotFoundError, Project, Tag};
-use std::collections::HashSet;
+use crate::config;
+use crate::error::{Error, Result};
+use crate::migrations;
+use crate::types::{
+    Collection, DataType, EmptyDataType, LanguageShare, Project, Remote, ScanSettings, Tag,
+    TagChanges, TagLink, TagSource,
+};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
-fn get_db() -> rusqlite::Connection {
-    let home = dirs::home_dir().unwrap();
-    let config_path = home.join(".config/folder_finder");
-    let db_path = config_path.join("db.sqlite");
+/// Number of prepared statements kept around for reuse
+const STATEMENT_CACHE_CAPACITY: usize = 64;
 
-    rusqlite::Connection::open(db_path).unwrap()
-}
+/// Visits older than this many days no longer count towards the frecency and are removed
+const VISIT_RETENTION_DAYS: i64 = 90;
 
-fn exists(data: &DataType) -> bool {
-    let conn = get_db();
-    let (table, column, value) = match data {
-        DataType::Collection(c) => ("collections", "path", c.path.clone()),
-        DataType::Project(p) => ("projects", "path", p.path.clone()),
-        DataType::Tag(t) => ("tags", "name", t.name.clone()),
-    };
-    let stmt = format!(
-        "SELECT EXISTS(SELECT 1 FROM {} WHERE {} = '{}')",
-        table, column, value
-    );
-    conn.query_row(stmt.as_str(), [], |row| row.get(0)).unwrap()
+thread_local! {
+    static STORE: OnceCell<Rc<Store>> = const { OnceCell::new() };
 }
 
-pub fn init(reset: bool) {
-    let home = dirs::home_dir().expect("Could not find home directory");
-    // TODO: platform agnostic path
-    let config_path = home.join(".config/folder_finder");
-    let db_path = config_path.join("db.sqlite");
-
-    if !config_path.exists() {
-        println!("Creating config folder");
-        std::fs::create_dir_all(&config_path)
-            .expect("Could not create config folder in '~/.config'");
-    }
-
-    if reset {
-        if db_path.exists() {
-            println!("Resetting database\nMoving current database to backup");
-            let backup_path = config_path.join(format!(
-                "db_{}.sqlite.bak",
-                chrono::Local::now().format("%Y-%m-%d")
-            ));
-            std::fs::rename(&db_path, &backup_path)
-                .expect("Failed to rename database file. Make sure you have the righ permissions");
+/// The database of this run, opened on first use
+pub fn store() -> Result<Rc<Store>> {
+    STORE.with(|cell| {
+        if let Some(store) = cell.get() {
+            return Ok(Rc::clone(store));
         }
-    }
-    if !db_path.exists() {
-        println!("Creating new database");
-        let conn = rusqlite::Connection::open(db_path).expect("Failed to create database");
-        conn.execute_batch(
-            "CREATE TABLE collections (
-                id INTEGER PRIMARY KEY,
-                path TEXT NOT NULL
-            );
-            CREATE TABLE projects (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL,
-                path TEXT NOT NULL,
-                collection_id INTEGER,
-                FOREIGN KEY (collection_id) REFERENCES collections(id) ON DELETE SET NULL
-            );
-            CREATE TABLE tags (
-                id INTEGER PRIMARY KEY,
-                name TEXT NOT NULL
-            );
-            CREATE TABLE project_tags (
-                data_id INTEGER NOT NULL,
-                tag_id INTEGER NOT NULL,
-                FOREIGN KEY (data_id) REFERENCES projects(id) ON DELETE CASCADE,
-                FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
-            );
-            CREATE TABLE collection_tags (
-                data_id INTEGER NOT NULL,
-                tag_id INTEGER NOT NULL,
-                FOREIGN KEY (data_id) REFERENCES collections(id) ON DELETE CASCADE,
-                FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
-            );",
-        )
-        .expect("Failed to create tables");
-    }
+        let store = Rc::new(Store::open()?);
+        let _ = cell.set(Rc::clone(&store));
+        Ok(store)
+    })
 }
 
-pub fn add_project_collection(project: &DataType, collection: &DataType) -> () {
-    let conn = get_db();
-    match (project, collection) {
-        (DataType::Project(_), DataType::Collection(_)) => {}
-        _ => panic!("Expected project and collection"),
-    }
-    let project_id = get_id(&project);
-    let collection_id = get_id(&collection);
-    let stmt = format!(
-        "UPDATE projects SET collection_id = {} WHERE id = {}",
-        collection_id, project_id
-    );
-    conn.execute(stmt.as_str(), []).unwrap();
+/// Access to the database through a single connection. Statements are prepared once and reused,
+/// so looking up many projects or tags in a row stays fast
+pub struct Store {
+    conn: rusqlite::Connection,
 }
 
-pub fn get_id(data: &DataType) -> i64 {
-    let conn = get_db();
-    let (table, column, value) = match data {
-        DataType::Collection(c) => ("collections", "path", c.path.clone()),
-        DataType::Project(p) => ("projects", "path", p.path.clone()),
-        DataType::Tag(t) => ("tags", "name", t.name.clone()),
-    };
-    let stmt = format!("SELECT id FROM {} WHERE {} = '{}'", table, column, value);
-    conn.query_row(stmt.as_str(), [], |row| row.get(0)).unwrap()
+/// The value that uniquely identifies an entity: the path for collections and projects, the name
+/// for tags
+fn key(data: &DataType) -> &str {
+    match data {
+        DataType::Collection(c) => &c.path,
+        DataType::Project(p) => &p.path,
+        DataType::Tag(t) => &t.name,
+    }
 }
 
-pub fn add(data: &DataType) -> Result<(), AlreadyExistsError> {
-    let conn = get_db();
-    if exists(data) {
-        return Err(AlreadyExistsError);
-    }
-    let (table, columns, values) = match data {
-        DataType::Collection(c) => ("collections", "path", format!("{}", c.path)),
-        DataType::Project(p) => (
-            "projects",
-            "name, path",
-            format!("{}', '{}", p.name, p.path),
-        ),
-        DataType::Tag(t) => ("tags", "name", format!("{}", t.name)),
+pub fn init(reset: bool) -> Result<()> {
+    let db_path = config::db_path()?;
+    let Some(db_dir) = db_path.parent() else {
+        return Err(Error::InvalidPath {
+            path: db_path,
+            source: std::io::ErrorKind::IsADirectory.into(),
+        });
     };
-    let stmt = format!("INSERT INTO {} ({}) VALUES ('{}')", table, columns, values);
-    conn.execute(stmt.as_str(), []).unwrap();
-    Ok(())
-}
 
-/// Delete and entity(e.g. project or tag) from database along with all its links
-pub fn delete(data: &DataType) -> Result<(), rusqlite::Error> {
-    let conn = get_db();
-    let (table, column, value) = match data {
-        DataType::Collection(c) => ("collections", "path", c.path.clone()),
-        DataType::Project(p) => ("projects", "path", p.path.clone()),
-        DataType::Tag(t) => ("tags", "name", t.name.clone()),
-    };
-    if !exists(data) {
-        return Ok(());
-    }
-    let res = conn.execute(
-        "DELETE FROM :table WHERE :column = :value",
-        named_params! {
-            ":table": table,
-            ":column": column,
-            ":value": value,
-        },
-    );
-    // NOTE: linked tags will be deleted by the database due to the CASCADE constraint
-    match res {
-        Ok(_) => Ok(()),
-        Err(e) => Err(e),
+    // NOTE: A relative path like `--db x.sqlite` has an empty parent, the current folder
+    if !db_dir.as_os_str().is_empty() && !db_dir.exists() {
+        println!("Creating folder {}", db_dir.display());
+        std::fs::create_dir_all(db_dir).map_err(|source| Error::Io {
+            path: db_dir.to_path_buf(),
+            source,
+        })?;
     }
-}
 
-pub fn list_collections() -> Result<Vec<Collection>, rusqlite::Error> {
-    let conn = get_db();
-    let mut stmt = conn.prepare("SELECT id, path FROM collections").unwrap();
-    let mut rows = stmt.query([])?;
-    let mut collections = Vec::new();
-
-    while let Some(row) = rows.next()? {
-        let id: i64 = row.get(0)?;
-        let path: String = row.get(1)?;
-        let tags = get_collection_tags(id);
-        collections.push(Collection {
-            path,
-            tags: tags.into_iter().collect(),
-        });
+    if reset && db_path.exists() {
+        println!("Resetting database\nMoving current database to backup");
+        let stem = db_path.file_stem().unwrap_or_default().to_string_lossy();
+        let backup_path = db_dir.join(format!(
+            "{}_{}.sqlite.bak",
+            stem,
+            chrono::Local::now().format("%Y-%m-%d")
+        ));
+        std::fs::rename(&db_path, &backup_path).map_err(|source| Error::Io {
+            path: db_path.clone(),
+            source,
+        })?;
+    }
+    if !db_path.exists() {
+        println!("Creating new database at {}", db_path.display());
     }
-    Ok(collections)
+    let mut conn = rusqlite::Connection::open(db_path)?;
+    migrations::migrate(&mut conn)?;
+    Ok(())
 }
 
-pub fn list_tags() -> Vec<Tag> {
-    let conn = get_db();
-    let mut stmt = conn.prepare("SELECT name FROM tags").unwrap();
-    let tag_names = stmt.query_map([], |row| row.get(0)).unwrap();
-    tag_names.map(|t| Tag { name: t.unwrap() }).collect()
+fn link_from_row(row: &rusqlite::Row) -> rusqlite::Result<TagLink> {
+    let source: String = row.get(1)?;
+    Ok(TagLink {
+        tag: Tag { name: row.get(0)? },
+        source: TagSource::parse(&source),
+        added_at: row.get(2)?,
+    })
 }
 
-pub fn add_tag(data: &DataType, tag: Tag, force: bool) -> Result<(), NotFoundError> {
-    let conn = get_db();
-    match data {
-        DataType::Tag(_) => {
-            panic!("Cannot add tag to tag")
+fn links_to_tags(links: &[TagLink]) -> HashSet<Tag> {
+    links.iter().map(|link| link.tag.clone()).collect()
+}
+
+impl Store {
+    fn open() -> Result<Store> {
+        let db_path = config::db_path()?;
+        if !db_path.exists() {
+            return Err(Error::NoDatabase(db_path));
+        }
+
+        Store::from_connection(rusqlite::Connection::open(db_path)?)
+    }
+
+    /// Use an already opened database, migrating it first. Lets tests use an in-memory database
+    pub fn from_connection(mut conn: rusqlite::Connection) -> Result<Store> {
+        migrations::migrate(&mut conn)?;
+        // NOTE: SQLite doesn't enforce foreign keys (and thus the CASCADE rules) unless enabled
+        // for every connection
+        conn.pragma_update(None, "foreign_keys", true)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        Ok(Store { conn })
+    }
+
+    /// Open the database read-only and without migrating it. Shell completions run on every
+    /// <TAB> and only read, so they shouldn't pay for or trigger migrations
+    pub fn open_read_only() -> Result<Store> {
+        let db_path = config::db_path()?;
+        if !db_path.exists() {
+            return Err(Error::NoDatabase(db_path));
         }
-        _ => {}
+        let conn = rusqlite::Connection::open_with_flags(
+            db_path,
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+        )?;
+        Ok(Store { conn })
+    }
+
+    fn exists(&self, data: &DataType) -> Result<bool> {
+        let stmt = match data {
+            DataType::Collection(_) => "SELECT EXISTS(SELECT 1 FROM collections WHERE path = ?1)",
+            DataType::Project(_) => "SELECT EXISTS(SELECT 1 FROM projects WHERE path = ?1)",
+            DataType::Tag(_) => "SELECT EXISTS(SELECT 1 FROM tags WHERE name = ?1)",
+        };
+        Ok(self
+            .conn
+            .prepare_cached(stmt)?
+            .query_row([key(data)], |row| row.get(0))?)
+    }
+
+    /// Errors with `Error::NotFound` for the kind of entity if it isn't stored
+    fn ensure_exists(&self, data: &DataType) -> Result<()> {
+        if self.exists(data)? {
+            return Ok(());
+        }
+        Err(Error::NotFound {
+            kind: data.kind(),
+            key: key(data).to_string(),
+        })
+    }
+
+    pub fn add_project_collection(&self, project: &DataType, collection: &DataType) -> Result<()> {
+        match (project, collection) {
+            (DataType::Project(_), DataType::Collection(_)) => {}
+            _ => panic!("Expected project and collection"),
+        }
+        let project_id = self.get_id(project)?;
+        let collection_id = self.get_id(collection)?;
+        self.conn
+            .prepare_cached("UPDATE projects SET collection_id = ?1 WHERE id = ?2")?
+            .execute(params![collection_id, project_id])?;
+        Ok(())
+    }
+
+    /// Errors with `Error::NotFound` if the entity isn't stored
+    pub fn get_id(&self, data: &DataType) -> Result<i64> {
+        let stmt = match data {
+            DataType::Collection(_) => "SELECT id FROM collections WHERE path = ?1",
+            DataType::Project(_) => "SELECT id FROM projects WHERE path = ?1",
+            DataType::Tag(_) => "SELECT id FROM tags WHERE name = ?1",
+        };
+        self.conn
+            .prepare_cached(stmt)?
+            .query_row([key(data)], |row| row.get(0))
+            .optional()?
+            .ok_or_else(|| Error::NotFound {
+                kind: data.kind(),
+                key: key(data).to_string(),
+            })
     }
-    if !exists(&data) {
-        return match data {
-            DataType::Collection(_) => Err(NotFoundError::Collection),
-            DataType::Project(_) => Err(NotFoundError::Project),
+
+    pub fn add(&self, data: &DataType) -> Result<()> {
+        let inserted = match data {
+            DataType::Collection(c) => self
+                .conn
+                .prepare_cached(
+                    "INSERT INTO collections (path, scan_depth, repos_only, include_hidden)
+                    VALUES (?1, ?2, ?3, ?4)
+                    ON CONFLICT DO NOTHING",
+                )?
+                .execute(params![
+                    c.path,
+                    c.scan.depth,
+                    c.scan.repos_only,
+                    c.scan.include_hidden
+                ]),
+            DataType::Project(p) => self
+                .conn
+                .prepare_cached(
+                    "INSERT INTO projects (name, path) VALUES (?1, ?2) ON CONFLICT DO NOTHING",
+                )?
+                .execute([&p.name, &p.path]),
+            DataType::Tag(t) => self
+                .conn
+                .prepare_cached("INSERT INTO tags (name) VALUES (?1) ON CONFLICT DO NOTHING")?
+                .execute([&t.name]),
+        }?;
+        if inserted == 0 {
+            return Err(Error::AlreadyExists {
+                kind: data.kind(),
+                key: key(data).to_string(),
+            });
+        }
+        if let DataType::Collection(c) = data {
+            self.set_ignores(data, &c.scan.ignore, true)?;
+        }
+        Ok(())
+    }
+
+    /// Delete and entity(e.g. project or tag) from database along with all its links
+    pub fn delete(&self, data: &DataType) -> Result<()> {
+        self.ensure_exists(data)?;
+        let stmt = match data {
+            DataType::Collection(_) => "DELETE FROM collections WHERE path = ?1",
+            DataType::Project(_) => "DELETE FROM projects WHERE path = ?1",
+            DataType::Tag(_) => "DELETE FROM tags WHERE name = ?1",
+        };
+        // NOTE: linked tags will be deleted by the database due to the CASCADE constraint
+        self.conn.prepare_cached(stmt)?.execute([key(data)])?;
+        Ok(())
+    }
+
+    /// All collections with their tags, in a single query
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        let mut ignores = self.get_all_ignores()?;
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT collections.id, collections.path, collections.scan_depth,
+                collections.repos_only, collections.include_hidden,
+                tags.name, collection_tags.source, collection_tags.added_at
+            FROM collections
+            LEFT JOIN collection_tags ON collection_tags.data_id = collections.id
+            LEFT JOIN tags ON tags.id = collection_tags.tag_id
+            ORDER BY collections.id",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut collections: Vec<Collection> = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            if collections.last().is_none_or(|c| c.id != Some(id)) {
+                collections.push(Collection {
+                    id: Some(id),
+                    path: row.get(1)?,
+                    scan: ScanSettings {
+                        depth: row.get(2)?,
+                        repos_only: row.get(3)?,
+                        include_hidden: row.get(4)?,
+                        ignore: ignores.remove(&id).unwrap_or_default(),
+                    },
+                    tags: HashSet::new(),
+                    tag_links: Vec::new(),
+                });
+            }
+            let collection = collections.last_mut().unwrap();
+            if let Some(name) = row.get::<_, Option<String>>(5)? {
+                let source: String = row.get(6)?;
+                collection.tags.insert(Tag { name: name.clone() });
+                collection.tag_links.push(TagLink {
+                    tag: Tag { name },
+                    source: TagSource::parse(&source),
+                    added_at: row.get(7)?,
+                });
+            }
+        }
+        Ok(collections)
+    }
+
+    pub fn list_tags(&self) -> Result<Vec<Tag>> {
+        let mut stmt = self.conn.prepare_cached("SELECT name FROM tags")?;
+        let tag_names = stmt.query_map([], |row| row.get(0))?;
+        Ok(tag_names
+            .map(|name| name.map(|name| Tag { name }))
+            .collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Link a tag to a project or collection. If the link already exists, the source is only
+    /// upgraded: manual tags replace imported and auto-detected ones, imported replace auto
+    pub fn add_tag(
+        &self,
+        data: &DataType,
+        tag: Tag,
+        force: bool,
+        source: &TagSource,
+    ) -> Result<()> {
+        if let DataType::Tag(_) = data {
+            panic!("Cannot add tag to tag")
+        }
+        self.ensure_exists(data)?;
+        let tag = DataType::Tag(tag);
+        if !self.exists(&tag)? {
+            if !force {
+                return Err(Error::NotFound {
+                    kind: tag.kind(),
+                    key: key(&tag).to_string(),
+                });
+            }
+            self.add(&tag)?;
+        }
+        let data_id = self.get_id(data)?;
+        let tag_id = self.get_id(&tag)?;
+        let stmt = match data {
+            DataType::Collection(_) => {
+                "INSERT INTO collection_tags (data_id, tag_id, source, added_at)
+                VALUES (?1, ?2, ?3, datetime('now'))
+                ON CONFLICT (data_id, tag_id) DO UPDATE
+                SET source = excluded.source, added_at = excluded.added_at
+                WHERE (CASE excluded.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)
+                    > (CASE collection_tags.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)"
+            }
+            DataType::Project(_) => {
+                "INSERT INTO project_tags (data_id, tag_id, source, added_at)
+                VALUES (?1, ?2, ?3, datetime('now'))
+                ON CONFLICT (data_id, tag_id) DO UPDATE
+                SET source = excluded.source, added_at = excluded.added_at
+                WHERE (CASE excluded.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)
+                    > (CASE project_tags.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)"
+            }
             _ => unreachable!(),
         };
+        // NOTE: No need to check if link already exists, the unique index on the link table turns
+        // duplicates into an update
+        self.conn
+            .prepare_cached(stmt)?
+            .execute(params![data_id, tag_id, source.to_string()])?;
+        Ok(())
+    }
+
+    /// Rename a tag, keeping all its links. The new name must not be in use yet
+    pub fn rename_tag(&self, old: &Tag, new: &Tag) -> Result<TagChanges> {
+        self.merge_tags(std::slice::from_ref(old), new)
     }
-    let tag = DataType::Tag(tag);
-    if !exists(&tag) {
-        if !force {
-            return Err(NotFoundError::Tag);
+
+    /// Move all links of the source tags to the destination tag and delete the source tags.
+    /// The destination tag is created if it doesn't exist yet. Everything happens in a single
+    /// transaction, so either all tags are merged or none are.
+    ///
+    /// Moved auto-detected links become manual, otherwise the next retag would remove them again
+    /// since no detector finds the new name. Where both tags were linked, the stronger source wins
+    /// as in `add_tag`
+    pub fn merge_tags(&self, sources: &[Tag], dest: &Tag) -> Result<TagChanges> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let mut source_ids = Vec::new();
+        for source in sources {
+            let id: Option<i64> = tx
+                .prepare_cached("SELECT id FROM tags WHERE name = ?1")?
+                .query_row([&source.name], |row| row.get(0))
+                .optional()?;
+            match id {
+                Some(id) => source_ids.push(id),
+                None => {
+                    return Err(Error::NotFound {
+                        kind: EmptyDataType::Tag,
+                        key: source.name.clone(),
+                    })
+                }
+            }
         }
-        add(&tag).unwrap();
-    }
-    let data_id = get_id(data);
-    let tag_id = get_id(&tag);
-    let table = match data {
-        DataType::Collection(_) => "collection_tags",
-        DataType::Project(_) => "project_tags",
-        _ => unreachable!(),
-    };
-    // NOTE: No need to check if link already exists, since the database will ignore duplicates,
-    // since it's a many-to-many relationship
-    let stmt = format!(
-        "INSERT INTO {} (data_id, tag_id) VALUES ({}, {})",
-        table, data_id, tag_id
-    );
-    let _ = conn.execute(stmt.as_str(), []);
-    Ok(())
-}
 
-fn get_project_tags(project_id: i64) -> HashSet<Tag> {
-    let conn = get_db();
-    let mut stmt = conn
-        .prepare(
-            "SELECT tags.name FROM tags
-            INNER JOIN project_tags ON tags.id = project_tags.tag_id
-            WHERE project_tags.data_id = ?",
-        )
-        .unwrap();
-    let tag_names = stmt.query_map([project_id], |row| row.get(0)).unwrap();
-    let tags: HashSet<Tag> = tag_names.map(|t| Tag { name: t.unwrap() }).collect();
-
-    let part_of_collection: bool = conn
-        .query_row(
-            "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ? AND collection_id IS NOT NULL)",
-            [project_id],
-            |row| row.get(0),
-        )
-        .unwrap();
-    if !part_of_collection {
-        return tags;
+        tx.execute(
+            "INSERT INTO tags (name) VALUES (?1) ON CONFLICT DO NOTHING",
+            [&dest.name],
+        )?;
+        let dest_id: i64 =
+            tx.query_row("SELECT id FROM tags WHERE name = ?1", [&dest.name], |row| {
+                row.get(0)
+            })?;
+
+        let mut projects = HashSet::new();
+        let mut collections = HashSet::new();
+        for source_id in source_ids.into_iter().filter(|&id| id != dest_id) {
+            let mut stmt =
+                tx.prepare_cached("SELECT data_id FROM project_tags WHERE tag_id = ?1")?;
+            let ids = stmt.query_map([source_id], |row| row.get::<_, i64>(0))?;
+            projects.extend(ids.collect::<rusqlite::Result<Vec<_>>>()?);
+            let mut stmt =
+                tx.prepare_cached("SELECT data_id FROM collection_tags WHERE tag_id = ?1")?;
+            let ids = stmt.query_map([source_id], |row| row.get::<_, i64>(0))?;
+            collections.extend(ids.collect::<rusqlite::Result<Vec<_>>>()?);
+
+            // NOTE: Links that already exist on the destination are collapsed by the unique index,
+            // keeping the stronger source
+            tx.execute(
+                "INSERT INTO project_tags (data_id, tag_id, source, added_at)
+                SELECT data_id, ?2, CASE WHEN source LIKE 'auto:%' THEN 'manual' ELSE source END,
+                    added_at
+                FROM project_tags WHERE tag_id = ?1
+                ON CONFLICT (data_id, tag_id) DO UPDATE
+                SET source = excluded.source, added_at = excluded.added_at
+                WHERE (CASE excluded.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)
+                    > (CASE project_tags.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)",
+                [source_id, dest_id],
+            )?;
+            tx.execute(
+                "INSERT INTO collection_tags (data_id, tag_id, source, added_at)
+                SELECT data_id, ?2, CASE WHEN source LIKE 'auto:%' THEN 'manual' ELSE source END,
+                    added_at
+                FROM collection_tags WHERE tag_id = ?1
+                ON CONFLICT (data_id, tag_id) DO UPDATE
+                SET source = excluded.source, added_at = excluded.added_at
+                WHERE (CASE excluded.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)
+                    > (CASE collection_tags.source WHEN 'manual' THEN 2 WHEN 'imported' THEN 1 ELSE 0 END)",
+                [source_id, dest_id],
+            )?;
+            // NOTE: the old links are deleted by the database due to the CASCADE constraint
+            tx.execute("DELETE FROM tags WHERE id = ?1", [source_id])?;
+        }
+        tx.commit()?;
+
+        Ok(TagChanges {
+            projects: projects.len(),
+            collections: collections.len(),
+        })
     }
 
-    let collection_id: i64 = conn
-        .query_row(
-            "SELECT collection_id FROM projects WHERE id = ?",
+    /// Remove the link between a project or collection and a tag, the tag itself is kept.
+    /// Errors with `Error::NotTagged` if the tag wasn't linked to the entity
+    pub fn remove_tag(&self, data: &DataType, tag: Tag) -> Result<()> {
+        if let DataType::Tag(_) = data {
+            panic!("Cannot remove tag from tag")
+        }
+        self.ensure_exists(data)?;
+        let stmt = match data {
+            DataType::Collection(_) => {
+                "DELETE FROM collection_tags
+                WHERE data_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)"
+            }
+            DataType::Project(_) => {
+                "DELETE FROM project_tags
+                WHERE data_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)"
+            }
+            DataType::Tag(_) => unreachable!(),
+        };
+        let removed = self
+            .conn
+            .prepare_cached(stmt)?
+            .execute(params![self.get_id(data)?, tag.name])?;
+        if removed == 0 {
+            return Err(Error::NotTagged {
+                tag: tag.name,
+                path: key(data).to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Remove the tags of a project or collection that came from a source, e.g. a detector, but
+    /// are no longer found there. Tags that were added by hand are never removed. Returns the
+    /// removed tags
+    pub fn remove_stale_tags(
+        &self,
+        data: &DataType,
+        found: &HashSet<Tag>,
+        from_source: impl Fn(&TagSource) -> bool,
+    ) -> Result<Vec<Tag>> {
+        let stale = self
+            .get_direct_links(data)?
+            .into_iter()
+            .filter(|link| from_source(&link.source) && !found.contains(&link.tag))
+            .map(|link| link.tag)
+            .collect::<Vec<_>>();
+        for tag in &stale {
+            self.remove_tag(data, tag.clone())?;
+        }
+        Ok(stale)
+    }
+
+    /// Tags linked directly to a project or collection along with their source, without the ones
+    /// inherited from a collection
+    pub fn get_direct_links(&self, data: &DataType) -> Result<Vec<TagLink>> {
+        if let DataType::Tag(_) = data {
+            panic!("Tags don't have tags")
+        }
+        self.ensure_exists(data)?;
+        let stmt = match data {
+            DataType::Collection(_) => {
+                "SELECT tags.name, collection_tags.source, collection_tags.added_at FROM tags
+                INNER JOIN collection_tags ON tags.id = collection_tags.tag_id
+                WHERE collection_tags.data_id = ?1"
+            }
+            DataType::Project(_) => {
+                "SELECT tags.name, project_tags.source, project_tags.added_at FROM tags
+                INNER JOIN project_tags ON tags.id = project_tags.tag_id
+                WHERE project_tags.data_id = ?1"
+            }
+            DataType::Tag(_) => unreachable!(),
+        };
+        let mut stmt = self.conn.prepare_cached(stmt)?;
+        let links = stmt
+            .query_map([self.get_id(data)?], link_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(links)
+    }
+
+    /// Tags linked directly to a project or collection, without the ones inherited from a
+    /// collection
+    pub fn get_direct_tags(&self, data: &DataType) -> Result<HashSet<Tag>> {
+        Ok(links_to_tags(&self.get_direct_links(data)?))
+    }
+
+    /// Add or remove ignore patterns of a collection
+    pub fn set_ignores(&self, collection: &DataType, patterns: &[String], add: bool) -> Result<()> {
+        let collection_id = self.get_id(collection)?;
+        let stmt = if add {
+            "INSERT INTO collection_ignores (collection_id, pattern) VALUES (?1, ?2)
+            ON CONFLICT DO NOTHING"
+        } else {
+            "DELETE FROM collection_ignores WHERE collection_id = ?1 AND pattern = ?2"
+        };
+        let mut stmt = self.conn.prepare_cached(stmt)?;
+        for pattern in patterns {
+            stmt.execute(params![collection_id, pattern])?;
+        }
+        Ok(())
+    }
+
+    /// Ignore patterns of all collections, by collection id
+    fn get_all_ignores(&self) -> Result<HashMap<i64, Vec<String>>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT collection_id, pattern FROM collection_ignores ORDER BY pattern",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut ignores: HashMap<i64, Vec<String>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            ignores.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+        Ok(ignores)
+    }
+
+    /// Names of the detectors that are turned off
+    pub fn list_disabled_detectors(&self) -> Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT name FROM disabled_detectors")?;
+        let names = stmt.query_map([], |row| row.get(0))?;
+        Ok(names.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn set_detector_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let stmt = if enabled {
+            "DELETE FROM disabled_detectors WHERE name = ?1"
+        } else {
+            "INSERT INTO disabled_detectors (name) VALUES (?1) ON CONFLICT DO NOTHING"
+        };
+        self.conn.execute(stmt, [name])?;
+        Ok(())
+    }
+
+    /// Replace the display name, description and aliases of a project
+    pub fn set_details(
+        &self,
+        project: &DataType,
+        display_name: Option<&str>,
+        description: Option<&str>,
+        aliases: &[String],
+    ) -> Result<()> {
+        let project_id = self.get_id(project)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.prepare_cached("UPDATE projects SET display_name = ?1, description = ?2 WHERE id = ?3")?
+            .execute(params![display_name, description, project_id])?;
+        tx.prepare_cached("DELETE FROM project_aliases WHERE project_id = ?1")?
+            .execute([project_id])?;
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO project_aliases (project_id, alias) VALUES (?1, ?2)
+                ON CONFLICT DO NOTHING",
+        )?;
+        for alias in aliases {
+            stmt.execute(params![project_id, alias])?;
+        }
+        drop(stmt);
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Aliases of all projects, by project id
+    fn get_all_aliases(&self) -> Result<HashMap<i64, Vec<String>>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT project_id, alias FROM project_aliases ORDER BY alias")?;
+        let mut rows = stmt.query([])?;
+        let mut aliases: HashMap<i64, Vec<String>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            aliases.entry(row.get(0)?).or_default().push(row.get(1)?);
+        }
+        Ok(aliases)
+    }
+
+    /// Record that a project was visited with `goto` or `jump`
+    pub fn record_visit(&self, project: &DataType) -> Result<()> {
+        let project_id = self.get_id(project)?;
+        self.conn.execute(
+            "INSERT INTO visits (project_id, visited_at) VALUES (?1, datetime('now'))",
             [project_id],
-            |row| row.get(0),
-        )
-        .unwrap();
+        )?;
+        self.conn.execute(
+            "DELETE FROM visits WHERE visited_at < datetime('now', ?1)",
+            [format!("-{} days", VISIT_RETENTION_DAYS)],
+        )?;
+        Ok(())
+    }
 
-    tags.union(&get_collection_tags(collection_id))
-        .cloned()
-        .collect()
-}
+    /// Frecency of all visited projects by project id. Like zoxide, recent visits weigh more: 4
+    /// in the last hour, 2 in the last day, 0.5 in the last week and 0.25 before that
+    pub fn get_frecencies(&self) -> Result<HashMap<i64, f64>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT project_id, SUM(
+                CASE
+                    WHEN visited_at >= datetime('now', '-1 hour') THEN 4.0
+                    WHEN visited_at >= datetime('now', '-1 day') THEN 2.0
+                    WHEN visited_at >= datetime('now', '-7 days') THEN 0.5
+                    ELSE 0.25
+                END)
+            FROM visits GROUP BY project_id",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut frecencies = HashMap::new();
+        while let Some(row) = rows.next()? {
+            frecencies.insert(row.get(0)?, row.get(1)?);
+        }
+        Ok(frecencies)
+    }
+
+    /// Replace the stored git remotes of a project
+    pub fn set_remotes(&self, project: &DataType, remotes: &[Remote]) -> Result<()> {
+        let project_id = self.get_id(project)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.prepare_cached("DELETE FROM project_remotes WHERE project_id = ?1")?
+            .execute([project_id])?;
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO project_remotes (project_id, name, url) VALUES (?1, ?2, ?3)",
+        )?;
+        for remote in remotes {
+            stmt.execute(params![project_id, remote.name, remote.url])?;
+        }
+        drop(stmt);
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Remotes of all projects, by project id
+    fn get_all_remotes(&self) -> Result<HashMap<i64, Vec<Remote>>> {
+        let mut stmt = self
+            .conn
+            .prepare_cached("SELECT project_id, name, url FROM project_remotes ORDER BY name")?;
+        let mut rows = stmt.query([])?;
+        let mut remotes: HashMap<i64, Vec<Remote>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            remotes.entry(row.get(0)?).or_default().push(Remote {
+                name: row.get(1)?,
+                url: row.get(2)?,
+            });
+        }
+        Ok(remotes)
+    }
+
+    /// Replace the stored language census of a project
+    pub fn set_languages(&self, project: &DataType, shares: &[(String, f64)]) -> Result<()> {
+        let project_id = self.get_id(project)?;
+        let tx = self.conn.unchecked_transaction()?;
+        tx.prepare_cached("DELETE FROM project_languages WHERE project_id = ?1")?
+            .execute([project_id])?;
+        let mut stmt = tx.prepare_cached(
+            "INSERT INTO project_languages (project_id, language, share) VALUES (?1, ?2, ?3)",
+        )?;
+        for (language, share) in shares {
+            stmt.execute(params![project_id, language, share])?;
+        }
+        drop(stmt);
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Language census of all projects by project id, largest share first
+    fn get_all_languages(&self) -> Result<HashMap<i64, Vec<LanguageShare>>> {
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT project_id, language, share FROM project_languages ORDER BY share DESC",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut languages: HashMap<i64, Vec<LanguageShare>> = HashMap::new();
+        while let Some(row) = rows.next()? {
+            languages
+                .entry(row.get(0)?)
+                .or_default()
+                .push(LanguageShare {
+                    language: row.get(1)?,
+                    share: row.get(2)?,
+                });
+        }
+        Ok(languages)
+    }
+
+    /// All projects with their collection and tags. The tags linked to the project and the ones
+    /// inherited from its collection come from a single query, one row per tag
+    pub fn list_projects(&self) -> Result<Vec<Project>> {
+        let mut remotes = self.get_all_remotes()?;
+        let mut languages = self.get_all_languages()?;
+        let mut aliases = self.get_all_aliases()?;
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT projects.id, projects.name, projects.path, projects.display_name,
+                projects.description, collections.id, collections.path, collections.scan_depth,
+                collections.repos_only, collections.include_hidden,
+                tags.name, links.source, links.added_at, links.inherited
+            FROM projects
+            LEFT JOIN collections ON projects.collection_id = collections.id
+            LEFT JOIN (
+                SELECT data_id AS project_id, tag_id, source, added_at, 0 AS inherited
+                FROM project_tags
+                UNION ALL
+                SELECT projects.id, collection_tags.tag_id, collection_tags.source,
+                    collection_tags.added_at, 1
+                FROM collection_tags
+                INNER JOIN projects ON projects.collection_id = collection_tags.data_id
+            ) AS links ON links.project_id = projects.id
+            LEFT JOIN tags ON tags.id = links.tag_id
+            ORDER BY projects.id, links.inherited",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut projects: Vec<Project> = Vec::new();
+
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            if projects.last().is_none_or(|p| p.id != Some(id)) {
+                let collection_id: Option<i64> = row.get(5)?;
+                let collection_path: Option<String> = row.get(6)?;
+                let collection = match collection_id.zip(collection_path) {
+                    Some((collection_id, path)) => Some(Collection {
+                        id: Some(collection_id),
+                        path,
+                        scan: ScanSettings {
+                            depth: row.get(7)?,
+                            repos_only: row.get(8)?,
+                            include_hidden: row.get(9)?,
+                            ignore: Vec::new(),
+                        },
+                        tags: HashSet::new(),
+                        tag_links: Vec::new(),
+                    }),
+                    None => None,
+                };
+                projects.push(Project {
+                    id: Some(id),
+                    name: row.get(1)?,
+                    display_name: row.get(3)?,
+                    description: row.get(4)?,
+                    aliases: aliases.remove(&id).unwrap_or_default(),
+                    path: row.get(2)?,
+                    collection,
+                    tags: HashSet::new(),
+                    tag_links: Vec::new(),
+                    remotes: remotes.remove(&id).unwrap_or_default(),
+                    languages: languages.remove(&id).unwrap_or_default(),
+                });
+            }
+            let proj = projects.last_mut().unwrap();
 
-fn get_collection_tags(collection_id: i64) -> HashSet<Tag> {
-    let conn = get_db();
-    let mut stmt = conn
-        .prepare(
-            format!(
-                "SELECT tags.name FROM tags
-            INNER JOIN collection_tags ON tags.id = collection_tags.tag_id
-            WHERE collection_tags.data_id = {}",
-                collection_id
-            )
-            .as_str(),
-        )
-        .unwrap();
-    let tag_names = stmt.query_map([], |row| row.get(0)).unwrap();
-    tag_names.map(|t| Tag { name: t.unwrap() }).collect()
+            let Some(name) = row.get::<_, Option<String>>(10)? else {
+                continue;
+            };
+            let source: String = row.get(11)?;
+            let link = TagLink {
+                tag: Tag { name },
+                source: TagSource::parse(&source),
+                added_at: row.get(12)?,
+            };
+            proj.tags.insert(link.tag.clone());
+            if row.get(13)? {
+                let collection = proj
+                    .collection
+                    .as_mut()
+                    .expect("Inherited tags come from the collection");
+                collection.tags.insert(link.tag.clone());
+                proj.tag_links.push(TagLink {
+                    source: TagSource::Inherited(collection.path.clone()),
+                    ..link.clone()
+                });
+                collection.tag_links.push(link);
+            } else {
+                proj.tag_links.push(link);
+            }
+        }
+        Ok(projects)
+    }
 }
 
-// pub fn add_project(path: PathBuf) -> Result<(), AlreadyExistsError> {
-//     let conn = get_db();
-//     let exists: bool = conn
-//         .query_row(
-//             "EXISTS(SELECT 1 FROM projects WHERE path = ?)",
-//             [path.to_str().unwrap()],
-//             |row| row.get(0),
-//         )
-//         .unwrap();
-//     if exists {
-//         return Err(AlreadyExistsError);
-//     }
-//     conn.execute(
-//         "INSERT INTO projects (name, path) VALUES (?, ?)",
-//         [
-//             path.file_name().unwrap().to_str().unwrap(),
-//             path.to_str().unwrap(),
-//         ],
-//     )
-//     .unwrap();
-//     Ok(())
-// }
-
-pub fn list_projects() -> Result<Vec<Project>, rusqlite::Error> {
-    let conn = get_db();
-    let mut stmt = conn.prepare("SELECT id, name, path FROM projects").unwrap();
-    let mut rows = stmt.query([])?;
-    let mut projects = Vec::new();
-
-    while let Some(row) = rows.next()? {
-        let id: i64 = row.get(0)?;
-        let name: String = row.get(1)?;
-        let path: String = row.get(2)?;
-        let tags = get_project_tags(id);
-        projects.push(Project {
-            name,
-            path,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> Store {
+        Store::from_connection(rusqlite::Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn project(path: &str) -> DataType {
+        DataType::Project(Project {
+            id: None,
+            name: path.rsplit('/').next().unwrap().to_string(),
+            display_name: None,
+            description: None,
+            aliases: Vec::new(),
+            path: path.to_string(),
             collection: None,
-            tags: tags.into_iter().collect(),
-        });
+            tags: HashSet::new(),
+            tag_links: Vec::new(),
+            remotes: Vec::new(),
+            languages: Vec::new(),
+        })
+    }
+
+    fn collection(path: &str) -> DataType {
+        DataType::Collection(Collection {
+            id: None,
+            path: path.to_string(),
+            scan: ScanSettings::default(),
+            tags: HashSet::new(),
+            tag_links: Vec::new(),
+        })
+    }
+
+    fn tag(name: &str) -> Tag {
+        Tag {
+            name: name.to_string(),
+        }
+    }
+
+    fn auto(rule: &str) -> TagSource {
+        TagSource::Auto(rule.to_string())
+    }
+
+    /// Sources of the tags linked directly to a project or collection, sorted by tag
+    fn sources(store: &Store, data: &DataType) -> Vec<(String, String)> {
+        let mut links = store
+            .get_direct_links(data)
+            .unwrap()
+            .into_iter()
+            .map(|link| (link.tag.name, link.source.to_string()))
+            .collect::<Vec<_>>();
+        links.sort();
+        links
+    }
+
+    fn pair(name: &str, source: &str) -> (String, String) {
+        (name.to_string(), source.to_string())
+    }
+
+    #[test]
+    fn add_tag_only_upgrades_the_source() {
+        let store = store();
+        let proj = project("/p/a");
+        store.add(&proj).unwrap();
+
+        store
+            .add_tag(&proj, tag("rust"), true, &auto("rust"))
+            .unwrap();
+        store
+            .add_tag(&proj, tag("rust"), true, &TagSource::Imported)
+            .unwrap();
+        assert_eq!(sources(&store, &proj), [pair("rust", "imported")]);
+        store
+            .add_tag(&proj, tag("rust"), true, &TagSource::Manual)
+            .unwrap();
+        assert_eq!(sources(&store, &proj), [pair("rust", "manual")]);
+
+        store
+            .add_tag(&proj, tag("rust"), true, &TagSource::Imported)
+            .unwrap();
+        store
+            .add_tag(&proj, tag("rust"), true, &auto("other"))
+            .unwrap();
+        assert_eq!(sources(&store, &proj), [pair("rust", "manual")]);
+    }
+
+    #[test]
+    fn add_tag_needs_force_for_new_tags() {
+        let store = store();
+        let proj = project("/p/a");
+        store.add(&proj).unwrap();
+        assert!(matches!(
+            store.add_tag(&proj, tag("new"), false, &TagSource::Manual),
+            Err(Error::NotFound { .. })
+        ));
+        assert!(sources(&store, &proj).is_empty());
+    }
+
+    #[test]
+    fn merge_keeps_the_stronger_source() {
+        let store = store();
+        let a = project("/p/a");
+        let b = project("/p/b");
+        store.add(&a).unwrap();
+        store.add(&b).unwrap();
+        store
+            .add_tag(&a, tag("js"), true, &TagSource::Manual)
+            .unwrap();
+        store
+            .add_tag(&a, tag("javascript"), true, &auto("javascript"))
+            .unwrap();
+        store
+            .add_tag(&b, tag("js"), true, &TagSource::Imported)
+            .unwrap();
+        store
+            .add_tag(&b, tag("javascript"), true, &TagSource::Manual)
+            .unwrap();
+
+        let changes = store.merge_tags(&[tag("js")], &tag("javascript")).unwrap();
+        assert_eq!((changes.projects, changes.collections), (2, 0));
+        assert_eq!(sources(&store, &a), [pair("javascript", "manual")]);
+        assert_eq!(sources(&store, &b), [pair("javascript", "manual")]);
+        assert_eq!(store.list_tags().unwrap(), [tag("javascript")]);
+    }
+
+    #[test]
+    fn rename_makes_auto_links_manual() {
+        let store = store();
+        let proj = project("/p/a");
+        let col = collection("/c");
+        store.add(&proj).unwrap();
+        store.add(&col).unwrap();
+        store
+            .add_tag(&proj, tag("javascript"), true, &auto("javascript"))
+            .unwrap();
+        store
+            .add_tag(&col, tag("javascript"), true, &TagSource::Imported)
+            .unwrap();
+
+        store.rename_tag(&tag("javascript"), &tag("js")).unwrap();
+        assert_eq!(sources(&store, &proj), [pair("js", "manual")]);
+        assert_eq!(sources(&store, &col), [pair("js", "imported")]);
+    }
+
+    #[test]
+    fn merge_is_all_or_nothing() {
+        let store = store();
+        let proj = project("/p/a");
+        store.add(&proj).unwrap();
+        store
+            .add_tag(&proj, tag("js"), true, &TagSource::Manual)
+            .unwrap();
+
+        assert!(matches!(
+            store.merge_tags(&[tag("js"), tag("missing")], &tag("javascript")),
+            Err(Error::NotFound { .. })
+        ));
+        assert_eq!(sources(&store, &proj), [pair("js", "manual")]);
+        assert_eq!(store.list_tags().unwrap(), [tag("js")]);
+    }
+
+    #[test]
+    fn projects_inherit_collection_tags() {
+        let store = store();
+        let col = collection("/c");
+        let proj = project("/c/a");
+        store.add(&col).unwrap();
+        store.add(&proj).unwrap();
+        store.add_project_collection(&proj, &col).unwrap();
+        store
+            .add_tag(&col, tag("work"), true, &TagSource::Manual)
+            .unwrap();
+        store
+            .add_tag(&proj, tag("rust"), true, &auto("rust"))
+            .unwrap();
+
+        let projects = store.list_projects().unwrap();
+        assert_eq!(projects.len(), 1);
+        let proj = &projects[0];
+        assert_eq!(proj.tags, HashSet::from([tag("work"), tag("rust")]));
+        let mut links = proj
+            .tag_links
+            .iter()
+            .map(|link| (link.tag.name.as_str(), link.source.to_string()))
+            .collect::<Vec<_>>();
+        links.sort();
+        assert_eq!(
+            links,
+            [
+                ("rust", "auto:rust".to_string()),
+                ("work", "inherited:/c".to_string())
+            ]
+        );
+        let col = proj.collection.as_ref().unwrap();
+        assert_eq!(col.tags, HashSet::from([tag("work")]));
     }
-    Ok(projects)
 }
@@ -0,0 +1,240 @@
+//! Detectors decide which tags apply to a project folder.
+//!
+//! Every source of auto-tags implements `Detector`: the auto-tag rules, the language census, git
+//! remotes, and external executables named `ff-detect-<name>` on the `PATH`. The `Registry`
+//! collects all of them and keeps track of which ones the user disabled.
+//!
+//! External detectors are called with the project folder as their only argument and must print
+//! a JSON array of detections to stdout, e.g. `[{"tag": "ansible", "evidence": "playbook.yml"}]`.
+//! Blank tags are dropped, and a detector that runs longer than `EXTERNAL_TIMEOUT` is stopped.
+
+use crate::error::Result;
+use crate::types::{Detection, Remote, Tag};
+use crate::{auto_tags, database, git, languages};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+const EXTERNAL_PREFIX: &str = "ff-detect-";
+
+/// How long an external detector may run before it is stopped, so a hanging one doesn't block
+/// adding and syncing projects
+const EXTERNAL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The project being evaluated
+pub struct Context<'a> {
+    pub dir: &'a Path,
+    /// Remotes of the project, read once and shared so detectors don't parse `.git/config` again
+    pub remotes: &'a [Remote],
+    /// Languages and file extensions, counted in a single walk since walking the project is slow
+    pub census: &'a languages::Census,
+}
+
+pub trait Detector {
+    /// Unique name, used as the source of the tags it adds, e.g. `auto:rust`
+    fn name(&self) -> &str;
+    /// Short description shown by `auto-tag list`
+    fn description(&self) -> String;
+    fn evaluate(&self, ctx: &Context) -> Vec<Detection>;
+}
+
+/// A detector implemented by an executable on the `PATH`
+pub struct External {
+    name: String,
+    path: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct ExternalDetection {
+    tag: String,
+    #[serde(default)]
+    evidence: String,
+}
+
+impl Detector for External {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> String {
+        format!("external: {}", self.path.display())
+    }
+
+    fn evaluate(&self, ctx: &Context) -> Vec<Detection> {
+        let Some(stdout) = self.run(ctx.dir) else {
+            return Vec::new();
+        };
+        match serde_json::from_slice::<Vec<ExternalDetection>>(&stdout) {
+            Ok(detections) => detections
+                .into_iter()
+                .map(|d| (d.tag.trim().to_string(), d.evidence))
+                .filter(|(name, _)| !name.is_empty())
+                .map(|(name, evidence)| Detection {
+                    tag: Tag { name },
+                    evidence,
+                })
+                .collect(),
+            Err(e) => {
+                eprintln!("Detector '{}' printed invalid output: {}", self.name, e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+impl External {
+    /// Run the detector on a folder and return what it printed, `None` if it failed or took
+    /// longer than `EXTERNAL_TIMEOUT`
+    fn run(&self, dir: &Path) -> Option<Vec<u8>> {
+        let mut child = match std::process::Command::new(&self.path)
+            .arg(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                eprintln!("Could not run detector '{}': {}", self.name, e);
+                return None;
+            }
+        };
+        // NOTE: Read on a separate thread, a detector printing more than the pipe buffer would
+        // otherwise never exit
+        let mut stdout = child.stdout.take().expect("stdout should be piped");
+        let reader = std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            let _ = stdout.read_to_end(&mut buf);
+            buf
+        });
+        let deadline = Instant::now() + EXTERNAL_TIMEOUT;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10))
+                }
+                Ok(None) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    eprintln!(
+                        "Detector '{}' took longer than {} seconds and was stopped",
+                        self.name,
+                        EXTERNAL_TIMEOUT.as_secs()
+                    );
+                    return None;
+                }
+                Err(e) => {
+                    eprintln!("Could not run detector '{}': {}", self.name, e);
+                    return None;
+                }
+            }
+        };
+        if !status.success() {
+            eprintln!("Detector '{}' failed with {}", self.name, status);
+            return None;
+        }
+        reader.join().ok()
+    }
+}
+
+/// Executables on the `PATH` starting with `ff-detect-`. When the same name is found in
+/// multiple folders, the first one wins, like the shell does
+fn find_external() -> Vec<External> {
+    let Some(path) = std::env::var_os("PATH") else {
+        return Vec::new();
+    };
+    let mut found: Vec<External> = Vec::new();
+    for dir in std::env::split_paths(&path) {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name
+                .to_str()
+                .and_then(|name| name.strip_prefix(EXTERNAL_PREFIX))
+            else {
+                continue;
+            };
+            if !is_executable(&entry.path()) || found.iter().any(|ext| ext.name == name) {
+                continue;
+            }
+            found.push(External {
+                name: name.to_string(),
+                path: entry.path(),
+            });
+        }
+    }
+    found.sort_by(|a, b| a.name.cmp(&b.name));
+    found
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+pub struct Registry {
+    detectors: Vec<Box<dyn Detector>>,
+    disabled: HashSet<String>,
+}
+
+impl Registry {
+    /// All built-in, rule based and external detectors
+    pub fn load() -> Result<Registry> {
+        let rules = auto_tags::load_rules()?;
+        let mut detectors: Vec<Box<dyn Detector>> = Vec::new();
+        detectors.push(Box::new(git::RemoteDetector));
+        detectors.push(Box::new(languages::LanguageDetector {
+            settings: rules.languages,
+        }));
+        for rule in rules.rules {
+            detectors.push(Box::new(rule));
+        }
+        for external in find_external() {
+            if detectors.iter().any(|d| d.name() == external.name) {
+                eprintln!(
+                    "Ignoring external detector {}, the name '{}' is already in use",
+                    external.path.display(),
+                    external.name
+                );
+                continue;
+            }
+            detectors.push(Box::new(external));
+        }
+        Ok(Registry {
+            detectors,
+            disabled: database::store()?
+                .list_disabled_detectors()?
+                .into_iter()
+                .collect(),
+        })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.detectors.iter().any(|d| d.name() == name)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled.contains(name)
+    }
+
+    pub fn all(&self) -> impl Iterator<Item = &dyn Detector> {
+        self.detectors.iter().map(|d| d.as_ref())
+    }
+
+    pub fn enabled(&self) -> impl Iterator<Item = &dyn Detector> {
+        self.all().filter(|d| self.is_enabled(d.name()))
+    }
+}
@@ -0,0 +1,171 @@
+//! The errors commands can fail with. Every error has a message for the user and an exit status,
+//! so scripts can tell what went wrong:
+//!
+//! | Status | Meaning                                                   |
+//! |--------|-----------------------------------------------------------|
+//! | 1      | Nothing matched or nothing was selected                   |
+//! | 2      | Invalid arguments, reported by clap                       |
+//! | 3      | Invalid input: a missing path, a bad query, glob or file  |
+//! | 4      | A project, collection, tag or detector isn't known        |
+//! | 5      | A project, collection or tag already exists               |
+//! | 6      | The database is missing, too new or failed                |
+//! | 7      | A file, the terminal or the home folder can't be used     |
+
+use crate::migrations::MigrationError;
+use crate::query;
+use crate::types::EmptyDataType;
+use std::path::PathBuf;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// No match for `jump` or `retag`
+    NoMatch,
+    /// The picker was closed without choosing anything
+    Cancelled,
+    /// A path from the command line that doesn't exist or can't be resolved
+    InvalidPath {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Paths are stored as text, so they must be valid UTF-8
+    NonUtf8Path(PathBuf),
+    InvalidQuery(query::ParseError),
+    InvalidPattern {
+        pattern: String,
+        source: globset::Error,
+    },
+    /// A TOML file that doesn't parse or has unknown keys
+    InvalidFile {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// The collection or project at a path, or the tag with a name, isn't in the database
+    NotFound {
+        kind: EmptyDataType,
+        key: String,
+    },
+    /// The tag exists, but isn't linked to the project or collection at the path
+    NotTagged {
+        tag: String,
+        path: String,
+    },
+    DetectorNotFound(String),
+    AlreadyExists {
+        kind: EmptyDataType,
+        key: String,
+    },
+    NoDatabase(PathBuf),
+    Migration(MigrationError),
+    Database(rusqlite::Error),
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Reading the answer to a prompt failed, e.g. because there is no terminal
+    Prompt(dialoguer::Error),
+    NoHomeDir,
+    /// The platform's folder for application data, e.g. `~/.local/share`, is unknown
+    NoDataDir,
+}
+
+impl Error {
+    /// Status to exit the process with, see the table in the module documentation
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoMatch | Error::Cancelled => 1,
+            Error::InvalidPath { .. }
+            | Error::NonUtf8Path(_)
+            | Error::InvalidQuery(_)
+            | Error::InvalidPattern { .. }
+            | Error::InvalidFile { .. } => 3,
+            Error::NotFound { .. } | Error::NotTagged { .. } | Error::DetectorNotFound(_) => 4,
+            Error::AlreadyExists { .. } => 5,
+            Error::NoDatabase(_) | Error::Migration(_) | Error::Database(_) => 6,
+            Error::Io { .. } | Error::Prompt(_) | Error::NoHomeDir | Error::NoDataDir => 7,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoMatch => write!(f, "No matching project"),
+            Error::Cancelled => write!(f, "Nothing selected"),
+            Error::InvalidPath { path, source } => {
+                write!(f, "Cannot use {}: {}", path.display(), source)
+            }
+            Error::NonUtf8Path(path) => {
+                write!(f, "Paths must be valid UTF-8: {}", path.display())
+            }
+            Error::InvalidQuery(e) => write!(f, "{}", e),
+            Error::InvalidPattern { pattern, source } => {
+                write!(f, "Invalid ignore pattern '{}': {}", pattern, source)
+            }
+            Error::InvalidFile { path, source } => {
+                write!(f, "Invalid file {}: {}", path.display(), source)
+            }
+            Error::NotFound {
+                kind: EmptyDataType::Tag,
+                key,
+            } => write!(f, "Tag not found: {}", key),
+            Error::NotFound { kind, key } => {
+                write!(f, "No {} at {}, add it with `{} add`", kind, key, kind)
+            }
+            Error::NotTagged { tag, path } => write!(f, "{} isn't tagged with {}", path, tag),
+            Error::DetectorNotFound(name) => write!(f, "Detector not found: {}", name),
+            Error::AlreadyExists { kind, key } => write!(f, "The {} {} already exists", kind, key),
+            Error::NoDatabase(path) => write!(
+                f,
+                "No database at {}, run `init` to create it",
+                path.display()
+            ),
+            Error::Migration(e) => write!(f, "{}", e),
+            Error::Database(e) => write!(f, "Database error: {}", e),
+            Error::Io { path, source } => {
+                write!(f, "Failed to access {}: {}", path.display(), source)
+            }
+            Error::Prompt(e) => write!(f, "Failed to read input: {}", e),
+            Error::NoHomeDir => write!(f, "Could not find the home folder"),
+            Error::NoDataDir => write!(f, "Could not find the data folder"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::InvalidPath { source, .. } | Error::Io { source, .. } => Some(source),
+            Error::InvalidPattern { source, .. } => Some(source),
+            Error::InvalidFile { source, .. } => Some(source),
+            Error::Database(e) => Some(e),
+            Error::Prompt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Database(e)
+    }
+}
+
+impl From<MigrationError> for Error {
+    fn from(e: MigrationError) -> Self {
+        Error::Migration(e)
+    }
+}
+
+impl From<query::ParseError> for Error {
+    fn from(e: query::ParseError) -> Self {
+        Error::InvalidQuery(e)
+    }
+}
+
+impl From<dialoguer::Error> for Error {
+    fn from(e: dialoguer::Error) -> Self {
+        Error::Prompt(e)
+    }
+}
@@ -0,0 +1,114 @@
+//! The `.folder_finder.toml` file in a project or collection folder, which can be checked in so
+//! everyone who clones a repository gets the same tags.
+//!
+//! ```toml
+//! name = "Folder Finder"
+//! description = "Keep track of project folders using tags"
+//! tags = ["cli", "tools"]
+//! aliases = ["ff"]
+//! ```
+//!
+//! Tags from the file are linked with the `imported` source and removed again when they are
+//! removed from the file. The name, description and aliases only apply to projects.
+
+use crate::error::{Error, Result};
+use crate::{database, types};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = ".folder_finder.toml";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FolderFile {
+    /// Shown instead of the folder name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Other names to find the project by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+fn file_path(dir: &Path) -> PathBuf {
+    dir.join(FILE_NAME)
+}
+
+/// Read the file in a folder, `None` if there is none
+pub fn read(dir: &Path) -> Result<Option<FolderFile>> {
+    let path = file_path(dir);
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(source) => return Err(Error::Io { path, source }),
+    };
+    toml::from_str(&contents)
+        .map(Some)
+        .map_err(|source| Error::InvalidFile { path, source })
+}
+
+/// Add tags to the file in a folder, creating it if needed. Comments in an existing file are lost,
+/// an invalid file is left alone
+pub fn write_tags(dir: &Path, tags: &[types::Tag]) -> Result<()> {
+    let mut file = read(dir)?.unwrap_or_default();
+    for tag in tags {
+        if !file.tags.iter().any(|t| t.to_lowercase() == tag.name) {
+            file.tags.push(tag.name.clone());
+        }
+    }
+    let contents = toml::to_string(&file).expect("The folder file should serialize");
+    let path = file_path(dir);
+    std::fs::write(&path, contents).map_err(|source| Error::Io { path, source })
+}
+
+/// Apply the file in the folder of a project or collection: link its tags, remove imported tags
+/// that are no longer listed and, for projects, store the name, description and aliases.
+/// Returns which tags changed. An unreadable file changes nothing, so a typo doesn't drop tags
+pub fn import(data: &types::DataType) -> Result<types::TagDiff> {
+    let dir = match data {
+        types::DataType::Project(p) => &p.path,
+        types::DataType::Collection(c) => &c.path,
+        types::DataType::Tag(_) => panic!("Tags don't have a folder"),
+    };
+    let file = match read(Path::new(dir)) {
+        Ok(file) => file.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Skipping import: {}", e);
+            return Ok(types::TagDiff::default());
+        }
+    };
+    let store = database::store()?;
+    let before = store.get_direct_tags(data)?;
+
+    let mut listed = HashSet::new();
+    let mut added = Vec::new();
+    for name in &file.tags {
+        let tag = types::Tag {
+            name: name.to_lowercase(),
+        };
+        store.add_tag(data, tag.clone(), true, &types::TagSource::Imported)?;
+        if listed.insert(tag.clone()) && !before.contains(&tag) {
+            added.push(types::Detection {
+                tag,
+                evidence: FILE_NAME.to_string(),
+            });
+        }
+    }
+    let removed = store.remove_stale_tags(data, &listed, |source| {
+        matches!(source, types::TagSource::Imported)
+    })?;
+
+    if let types::DataType::Project(_) = data {
+        store.set_details(
+            data,
+            file.name.as_deref(),
+            file.description.as_deref(),
+            &file.aliases,
+        )?;
+    }
+    Ok(types::TagDiff { added, removed })
+}
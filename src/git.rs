@@ -0,0 +1,181 @@
+//! Reading remotes from a repository's git config, without shelling out to git
+
+use crate::detectors::{Context, Detector};
+use crate::types::{Detection, Remote, Tag};
+use std::path::{Path, PathBuf};
+
+/// A remote url split into its parts, e.g. `git@github.com:owner/repo.git`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteUrl {
+    pub host: String,
+    /// Everything between the host and the repository, e.g. nested gitlab groups `group/sub`
+    pub owner: String,
+    pub repo: String,
+}
+
+impl RemoteUrl {
+    pub fn parse(url: &str) -> Option<RemoteUrl> {
+        let url = url.trim();
+        let rest = if let Some((_scheme, rest)) = url.split_once("://") {
+            // NOTE: e.g. https://github.com/owner/repo or ssh://git@host:22/owner/repo
+            rest.to_string()
+        } else if let Some((host, path)) = url.split_once(':') {
+            // NOTE: scp-like syntax, e.g. git@github.com:owner/repo
+            format!("{}/{}", host, path)
+        } else {
+            return None;
+        };
+        let (host, path) = rest.split_once('/')?;
+        let host = host.rsplit('@').next()?;
+        let host = host.split(':').next()?.to_lowercase();
+        let path = path.trim_matches('/');
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let (owner, repo) = path.rsplit_once('/')?;
+        if host.is_empty() || owner.is_empty() || repo.is_empty() {
+            return None;
+        }
+        Some(RemoteUrl {
+            host,
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+        })
+    }
+
+    /// Tag for the hosting provider of the remote
+    pub fn provider(&self) -> &'static str {
+        match self.host.as_str() {
+            "github.com" => "github",
+            "gitlab.com" => "gitlab",
+            _ => "self-hosted",
+        }
+    }
+}
+
+impl std::fmt::Display for RemoteUrl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.host, self.owner, self.repo)
+    }
+}
+
+/// Tags a project by the hosting provider and owner of each remote
+pub struct RemoteDetector;
+
+impl Detector for RemoteDetector {
+    fn name(&self) -> &str {
+        "git-remote"
+    }
+
+    fn description(&self) -> String {
+        "hosting provider and owner of git remotes".to_string()
+    }
+
+    fn evaluate(&self, ctx: &Context) -> Vec<Detection> {
+        ctx.remotes
+            .iter()
+            .filter_map(|remote| RemoteUrl::parse(&remote.url).map(|url| (remote, url)))
+            .flat_map(|(remote, url)| {
+                [url.provider().to_string(), url.owner.to_lowercase()].map(|name| Detection {
+                    tag: Tag { name },
+                    evidence: format!("remote {}", remote.name),
+                })
+            })
+            .collect()
+    }
+}
+
+/// The git directory of a project, following `gitdir:` files used by worktrees and submodules
+fn git_dir(project: &Path) -> Option<PathBuf> {
+    let dot_git = project.join(".git");
+    if dot_git.is_dir() {
+        return Some(dot_git);
+    }
+    let contents = std::fs::read_to_string(&dot_git).ok()?;
+    let dir = contents.trim().strip_prefix("gitdir:")?.trim();
+    Some(project.join(dir))
+}
+
+/// Remotes in the `.git/config` of a project, empty if it isn't a git repository
+pub fn remotes(project: &Path) -> Vec<Remote> {
+    let Some(dir) = git_dir(project) else {
+        return Vec::new();
+    };
+    // NOTE: Worktrees keep the config in the main repository
+    let config = std::fs::read_to_string(dir.join("config")).or_else(|_| {
+        let common = std::fs::read_to_string(dir.join("commondir"))?;
+        std::fs::read_to_string(dir.join(common.trim()).join("config"))
+    });
+    match config {
+        Ok(config) => parse_config(&config),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn parse_config(config: &str) -> Vec<Remote> {
+    let mut remotes = Vec::new();
+    let mut current: Option<String> = None;
+    for line in config.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            // NOTE: Section headers look like [remote "origin"]
+            current = line
+                .strip_prefix("[remote")
+                .and_then(|rest| rest.trim().strip_prefix('"'))
+                .and_then(|rest| rest.split_once('"'))
+                .map(|(name, _)| name.to_string());
+        } else if let (Some(name), Some((key, value))) = (&current, line.split_once('=')) {
+            if key.trim() == "url" {
+                remotes.push(Remote {
+                    name: name.clone(),
+                    url: value.trim().trim_matches('"').to_string(),
+                });
+            }
+        }
+    }
+    remotes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(url: &str) -> Option<(String, String, String)> {
+        RemoteUrl::parse(url).map(|url| (url.host, url.owner, url.repo))
+    }
+
+    fn parts(host: &str, owner: &str, repo: &str) -> Option<(String, String, String)> {
+        Some((host.to_string(), owner.to_string(), repo.to_string()))
+    }
+
+    #[test]
+    fn parses_common_url_forms() {
+        let expected = parts("github.com", "owner", "repo");
+        assert_eq!(parsed("https://github.com/owner/repo"), expected);
+        assert_eq!(parsed("https://github.com/owner/repo.git/"), expected);
+        assert_eq!(parsed("git@github.com:owner/repo.git"), expected);
+        assert_eq!(parsed("ssh://git@GitHub.com:22/owner/repo"), expected);
+        assert_eq!(parsed("https://user@github.com/owner/repo"), expected);
+    }
+
+    #[test]
+    fn keeps_nested_groups_in_the_owner() {
+        assert_eq!(
+            parsed("git@gitlab.com:group/sub/repo.git"),
+            parts("gitlab.com", "group/sub", "repo")
+        );
+    }
+
+    #[test]
+    fn rejects_urls_without_owner_or_repo() {
+        assert_eq!(parsed("/home/me/repo"), None);
+        assert_eq!(parsed("https://github.com/repo"), None);
+        assert_eq!(parsed("git@github.com:"), None);
+    }
+
+    #[test]
+    fn provider_from_host() {
+        let provider = |url| RemoteUrl::parse(url).unwrap().provider();
+        assert_eq!(provider("git@github.com:o/r"), "github");
+        assert_eq!(provider("https://gitlab.com/o/r"), "gitlab");
+        assert_eq!(provider("https://git.example.org/o/r"), "self-hosted");
+    }
+}
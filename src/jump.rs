@@ -0,0 +1,151 @@
+//! Go to a project without the picker. Every term is scored against the project's name, aliases,
+//! tags and path, and the scores are weighed by frecency: how often and how recently a project
+//! was visited with `goto` or `jump`.
+
+use crate::database;
+use crate::error::{Error, Result};
+use crate::picker;
+use crate::types;
+use clap::{arg, Command};
+use std::io::IsTerminal;
+
+/// Matches scoring at least this share of the best match are too close to pick one automatically
+const AMBIGUITY_RATIO: f64 = 0.9;
+
+pub fn command() -> Command {
+    Command::new("jump")
+        .about("Print the path of the best project matching all terms")
+        .long_about(
+            "Print the path of the best project matching all terms.\n\
+            Terms are matched against the project's name, aliases, tags and path segments, and \
+            matches are ranked by how well they match and how often they were visited. When the \
+            best matches are too close, a picker is shown. Without terms the most visited project \
+            is printed. Exits with status 1 if nothing matches.\n\
+            Use the `ff` function from `shell-init` to change into the project.",
+        )
+        .arg(arg!([TERM]).num_args(1..))
+        .arg(arg!(-a --all "Print all matches, best first"))
+}
+
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
+    jump(
+        args.get_many::<String>("TERM")
+            .unwrap_or_default()
+            .map(|term| term.to_lowercase())
+            .collect(),
+        args.get_flag("all"),
+    )
+}
+
+/// Sort projects by frecency, most visited first. Projects with the same frecency keep their order
+pub fn sort_by_frecency(projects: &mut [types::Project]) -> Result<()> {
+    let frecencies = database::store()?.get_frecencies()?;
+    let frecency = |proj: &types::Project| {
+        proj.id
+            .and_then(|id| frecencies.get(&id))
+            .copied()
+            .unwrap_or_default()
+    };
+    projects.sort_by(|a, b| frecency(b).total_cmp(&frecency(a)));
+    Ok(())
+}
+
+/// How well a name matches a term: exactly, at the start or somewhere in it
+fn name_score(name: &str, term: &str) -> f64 {
+    let name = name.to_lowercase();
+    if name == term {
+        10.0
+    } else if name.starts_with(term) {
+        6.0
+    } else if name.contains(term) {
+        4.0
+    } else {
+        0.0
+    }
+}
+
+/// Score of a single term for a project, 0 if it doesn't match at all. Names and aliases count
+/// the most, then tags, then the folders the project is in
+fn term_score(proj: &types::Project, term: &str) -> f64 {
+    let names = [proj.shown_name(), proj.name.as_str()]
+        .into_iter()
+        .chain(proj.aliases.iter().map(|a| a.as_str()))
+        .map(|name| name_score(name, term));
+    let tags = proj.tags.iter().map(|tag| {
+        if tag.name.to_lowercase() == term {
+            8.0
+        } else {
+            0.0
+        }
+    });
+    let segments = std::path::Path::new(&proj.path)
+        .parent()
+        .into_iter()
+        .flat_map(|parent| parent.iter())
+        .map(|segment| name_score(&segment.to_string_lossy(), term) / 3.0);
+    names.chain(tags).chain(segments).fold(0.0, f64::max)
+}
+
+/// Score of a project for all terms, `None` if any term doesn't match
+fn score(proj: &types::Project, terms: &[String], frecency: f64) -> Option<f64> {
+    let mut total = 1.0;
+    for term in terms {
+        let score = term_score(proj, term);
+        if score == 0.0 {
+            return None;
+        }
+        total += score;
+    }
+    // NOTE: The frecency grows quickly for projects in daily use, so only count it logarithmically
+    Some(total * (1.0 + frecency.ln_1p()))
+}
+
+fn jump(terms: Vec<String>, all: bool) -> Result<()> {
+    let store = database::store()?;
+    let frecencies = store.get_frecencies()?;
+    let mut matches = store
+        .list_projects()?
+        .into_iter()
+        .filter_map(|proj| {
+            let frecency = proj
+                .id
+                .and_then(|id| frecencies.get(&id))
+                .copied()
+                .unwrap_or_default();
+            score(&proj, &terms, frecency).map(|score| (proj, score))
+        })
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        return Err(Error::NoMatch);
+    }
+    matches.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.path.cmp(&b.0.path)));
+
+    if all {
+        matches
+            .iter()
+            .for_each(|(proj, _)| println!("{}", proj.path));
+        return Ok(());
+    }
+
+    let best = matches[0].1;
+    let close = matches
+        .iter()
+        .take_while(|(_, score)| *score >= best * AMBIGUITY_RATIO)
+        .count();
+    // NOTE: Scripts without a terminal get the best match instead of a picker
+    let index = if close > 1 && std::io::stdin().is_terminal() {
+        let lines = matches[..close]
+            .iter()
+            .map(|(proj, _)| format!("{} | {}", proj, proj.path))
+            .collect::<Vec<_>>();
+        picker::pick("Project", &lines)?.ok_or(Error::Cancelled)?
+    } else {
+        0
+    };
+    let proj = matches.swap_remove(index).0;
+    println!("{}", proj.path);
+    // NOTE: The path is already printed, a failed visit shouldn't keep the shell wrapper from
+    // changing into it
+    let _ = store.record_visit(&types::DataType::Project(proj));
+    Ok(())
+}
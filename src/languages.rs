@@ -0,0 +1,196 @@
+//! Language census: the share of each programming language in a project, by file size
+
+use crate::detectors::{Context, Detector};
+use crate::types::{Detection, Tag};
+use ignore::{DirEntry, WalkBuilder};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Folders that are skipped even when they aren't in a `.gitignore`
+const SKIPPED_DIRS: [&str; 6] = [
+    "target",
+    "node_modules",
+    ".venv",
+    "venv",
+    "__pycache__",
+    "build",
+];
+
+/// File extensions and the language (tag) they belong to
+const EXTENSIONS: [(&str, &str); 42] = [
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("svelte", "svelte"),
+    ("vue", "vue"),
+    ("go", "go"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "c++"),
+    ("cpp", "c++"),
+    ("cxx", "c++"),
+    ("hpp", "c++"),
+    ("hh", "c++"),
+    ("cs", "c#"),
+    ("fs", "f#"),
+    ("java", "java"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("scala", "scala"),
+    ("swift", "swift"),
+    ("rb", "ruby"),
+    ("php", "php"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("erl", "erlang"),
+    ("hs", "haskell"),
+    ("ml", "ocaml"),
+    ("zig", "zig"),
+    ("lua", "lua"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("nix", "nix"),
+    ("tex", "latex"),
+    ("jl", "julia"),
+    ("r", "r"),
+];
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_threshold() -> f64 {
+    0.1
+}
+
+/// The `[languages]` section of the rules file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Minimum share (0 to 1) of a language before the project is tagged with it
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            enabled: true,
+            threshold: default_threshold(),
+        }
+    }
+}
+
+/// Tags a project with every language above the threshold
+pub struct LanguageDetector {
+    pub settings: Settings,
+}
+
+impl Detector for LanguageDetector {
+    fn name(&self) -> &str {
+        "languages"
+    }
+
+    fn description(&self) -> String {
+        format!(
+            "languages making up at least {}% of the code",
+            self.settings.threshold * 100.0
+        )
+    }
+
+    fn evaluate(&self, ctx: &Context) -> Vec<Detection> {
+        if !self.settings.enabled {
+            return Vec::new();
+        }
+        ctx.census
+            .languages
+            .iter()
+            .filter(|(_, share)| *share >= self.settings.threshold)
+            .map(|(language, share)| Detection {
+                tag: Tag {
+                    name: language.clone(),
+                },
+                evidence: format!("{:.1}% of the code", share * 100.0),
+            })
+            .collect()
+    }
+}
+
+fn language(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    EXTENSIONS
+        .iter()
+        .find(|(ext, _)| *ext == extension)
+        .map(|(_, language)| *language)
+}
+
+/// The files in a project. Respects `.gitignore` files and skips hidden and dependency/build
+/// folders
+fn walk(dir: &Path) -> impl Iterator<Item = DirEntry> {
+    WalkBuilder::new(dir)
+        .require_git(false)
+        .filter_entry(|entry| {
+            !(entry.file_type().is_some_and(|t| t.is_dir())
+                && SKIPPED_DIRS.iter().any(|skip| entry.file_name() == *skip))
+        })
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+}
+
+/// What a single walk over a project found
+#[derive(Debug, Clone, Default)]
+pub struct Census {
+    /// Share of every language by bytes, largest first
+    pub languages: Vec<(String, f64)>,
+    /// Number of files per extension, only counting files at most `extension_depth` folders deep
+    pub extensions: HashMap<String, usize>,
+}
+
+/// Count the languages and file extensions of a project
+pub fn census(dir: &Path, extension_depth: usize) -> Census {
+    let mut bytes: HashMap<&str, u64> = HashMap::new();
+    let mut extensions: HashMap<String, usize> = HashMap::new();
+    for entry in walk(dir) {
+        // NOTE: Files directly in the project are at depth 1
+        if entry.depth() <= extension_depth + 1 {
+            if let Some(extension) = entry.path().extension() {
+                *extensions
+                    .entry(extension.to_string_lossy().into_owned())
+                    .or_default() += 1;
+            }
+        }
+        let Some(language) = language(entry.path()) else {
+            continue;
+        };
+        let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+        *bytes.entry(language).or_default() += size;
+    }
+
+    let total: u64 = bytes.values().sum();
+    if total == 0 {
+        return Census {
+            languages: Vec::new(),
+            extensions,
+        };
+    }
+    let mut shares = bytes
+        .into_iter()
+        .map(|(language, size)| (language.to_string(), size as f64 / total as f64))
+        .collect::<Vec<_>>();
+    shares.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Census {
+        languages: shares,
+        extensions,
+    }
+}
@@ -2,14 +2,47 @@ use clap::{arg, command, Command};
 
 pub mod auto_tags;
 pub mod collections;
+pub mod completions;
+pub mod config;
 pub mod database;
+pub mod detectors;
+pub mod error;
+pub mod folder_file;
+pub mod git;
+pub mod jump;
+pub mod languages;
+pub mod migrations;
+pub mod output;
+pub mod picker;
 pub mod projects;
+pub mod query;
+pub mod shell;
 pub mod tags;
 pub mod types;
 
-fn main() {
-    let matches = command!()
+fn cli() -> Command {
+    command!()
         .arg_required_else_help(true)
+        .arg(
+            arg!(--format <FORMAT> "Output format of list commands")
+                .global(true)
+                .value_parser(output::FORMATS)
+                .default_value("table"),
+        )
+        .arg(
+            arg!(--db <PATH>)
+                .help(format!(
+                    "Database to use instead of the default, also set by {}",
+                    config::DB_ENV
+                ))
+                .global(true)
+                .value_parser(clap::value_parser!(std::path::PathBuf)),
+        )
+        .arg(
+            arg!(--profile <NAME> "Use a separate database, configured in [profiles.NAME] of the config file")
+                .global(true)
+                .conflicts_with("db"),
+        )
         .subcommand(
             Command::new("init")
                 .about("Setup the database")
@@ -18,18 +51,50 @@ fn main() {
         .subcommand(collections::command())
         .subcommand(projects::command())
         .subcommand(tags::command())
-        .get_matches();
+        .subcommand(auto_tags::command())
+        .subcommand(jump::command())
+        .subcommand(shell::command())
+        .subcommand(completions::command())
+}
 
-    match matches.subcommand() {
+fn main() {
+    if std::env::var_os(completions::VAR).is_some() {
+        completions::select_db(std::env::args_os());
+    }
+    clap_complete::CompleteEnv::with_factory(cli)
+        .var(completions::VAR)
+        .complete();
+    let matches = cli().get_matches();
+    let res = config::select_db(
+        matches
+            .get_one::<std::path::PathBuf>("db")
+            .map(|p| p.as_path()),
+        matches.get_one::<String>("profile").map(|p| p.as_str()),
+    )
+    .and_then(|()| match matches.subcommand() {
         Some(("init", args)) => init(args),
         Some(("tag", args)) => tags::run(args),
+        Some(("auto-tag", args)) => auto_tags::run(args),
         Some(("collection", args)) => collections::run(args),
         Some(("project", args)) => projects::run(args),
+        Some(("jump", args)) => jump::run(args),
+        Some(("shell-init", args)) => {
+            shell::run(args);
+            Ok(())
+        }
+        Some(("completions", args)) => {
+            completions::run(args);
+            Ok(())
+        }
         _ => panic!("Should be handled by clap itself"),
+    });
+    if let Err(e) = res {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
     }
 }
 
-fn init(args: &clap::ArgMatches) {
+fn init(args: &clap::ArgMatches) -> error::Result<()> {
     let reset = args.get_flag("reset");
-    database::init(reset);
+    database::init(reset)
 }
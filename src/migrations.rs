@@ -0,0 +1,270 @@
+//! Versioned schema migrations.
+//!
+//! The schema version is stored in `PRAGMA user_version`. Every entry in `MIGRATIONS` upgrades the
+//! database by one version, so new steps must only ever be appended to the end of the list.
+
+use rusqlite::Connection;
+
+const MIGRATIONS: &[&str] = &[
+    // 1: Initial schema. Uses IF NOT EXISTS since databases created before migrations existed
+    // already have these tables, but are still at version 0
+    "CREATE TABLE IF NOT EXISTS collections (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS projects (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        path TEXT NOT NULL,
+        collection_id INTEGER,
+        FOREIGN KEY (collection_id) REFERENCES collections(id) ON DELETE SET NULL
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS project_tags (
+        data_id INTEGER NOT NULL,
+        tag_id INTEGER NOT NULL,
+        FOREIGN KEY (data_id) REFERENCES projects(id) ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+    );
+    CREATE TABLE IF NOT EXISTS collection_tags (
+        data_id INTEGER NOT NULL,
+        tag_id INTEGER NOT NULL,
+        FOREIGN KEY (data_id) REFERENCES collections(id) ON DELETE CASCADE,
+        FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+    );",
+    // 2: Unique paths, tag names and links. Foreign keys were never enforced before this version,
+    // so dangling rows and duplicates are cleaned up before the indexes are created
+    "DELETE FROM project_tags
+        WHERE data_id NOT IN (SELECT id FROM projects) OR tag_id NOT IN (SELECT id FROM tags);
+    DELETE FROM collection_tags
+        WHERE data_id NOT IN (SELECT id FROM collections) OR tag_id NOT IN (SELECT id FROM tags);
+    UPDATE projects SET collection_id = NULL
+        WHERE collection_id NOT IN (SELECT id FROM collections);
+
+    UPDATE project_tags SET tag_id = (
+        SELECT MIN(keep.id) FROM tags AS keep
+        WHERE keep.name = (SELECT name FROM tags WHERE id = project_tags.tag_id)
+    );
+    UPDATE collection_tags SET tag_id = (
+        SELECT MIN(keep.id) FROM tags AS keep
+        WHERE keep.name = (SELECT name FROM tags WHERE id = collection_tags.tag_id)
+    );
+    DELETE FROM tags WHERE id NOT IN (SELECT MIN(id) FROM tags GROUP BY name);
+
+    UPDATE projects SET collection_id = (
+        SELECT MIN(keep.id) FROM collections AS keep
+        WHERE keep.path = (SELECT path FROM collections WHERE id = projects.collection_id)
+    ) WHERE collection_id IS NOT NULL;
+    UPDATE collection_tags SET data_id = (
+        SELECT MIN(keep.id) FROM collections AS keep
+        WHERE keep.path = (SELECT path FROM collections WHERE id = collection_tags.data_id)
+    );
+    DELETE FROM collections WHERE id NOT IN (SELECT MIN(id) FROM collections GROUP BY path);
+
+    UPDATE project_tags SET data_id = (
+        SELECT MIN(keep.id) FROM projects AS keep
+        WHERE keep.path = (SELECT path FROM projects WHERE id = project_tags.data_id)
+    );
+    DELETE FROM projects WHERE id NOT IN (SELECT MIN(id) FROM projects GROUP BY path);
+
+    DELETE FROM project_tags
+        WHERE rowid NOT IN (SELECT MIN(rowid) FROM project_tags GROUP BY data_id, tag_id);
+    DELETE FROM collection_tags
+        WHERE rowid NOT IN (SELECT MIN(rowid) FROM collection_tags GROUP BY data_id, tag_id);
+
+    CREATE UNIQUE INDEX collections_path ON collections(path);
+    CREATE UNIQUE INDEX projects_path ON projects(path);
+    CREATE UNIQUE INDEX tags_name ON tags(name);
+    CREATE UNIQUE INDEX project_tags_link ON project_tags(data_id, tag_id);
+    CREATE UNIQUE INDEX collection_tags_link ON collection_tags(data_id, tag_id);",
+    // 3: Git remotes of projects
+    "CREATE TABLE project_remotes (
+        project_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        url TEXT NOT NULL,
+        UNIQUE (project_id, name),
+        FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+    );",
+    // 4: Share of each language in a project, from the language census
+    "CREATE TABLE project_languages (
+        project_id INTEGER NOT NULL,
+        language TEXT NOT NULL,
+        share REAL NOT NULL,
+        UNIQUE (project_id, language),
+        FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+    );",
+    // 5: Where tag links came from. Existing links can't be told apart anymore, so they are all
+    // treated as manual, which makes sure they are never removed automatically
+    "ALTER TABLE project_tags ADD COLUMN source TEXT NOT NULL DEFAULT 'manual';
+    ALTER TABLE project_tags ADD COLUMN added_at TEXT;
+    ALTER TABLE collection_tags ADD COLUMN source TEXT NOT NULL DEFAULT 'manual';
+    ALTER TABLE collection_tags ADD COLUMN added_at TEXT;",
+    // 6: Detectors the user turned off with `auto-tag disable`
+    "CREATE TABLE disabled_detectors (
+        name TEXT PRIMARY KEY
+    );",
+    // 7: How the folders of a collection are scanned for projects
+    "ALTER TABLE collections ADD COLUMN scan_depth INTEGER NOT NULL DEFAULT 1;
+    ALTER TABLE collections ADD COLUMN repos_only INTEGER NOT NULL DEFAULT 0;",
+    // 8: Folders to skip when scanning a collection. Hidden folders used to be included, so
+    // existing collections keep doing that
+    "ALTER TABLE collections ADD COLUMN include_hidden INTEGER NOT NULL DEFAULT 0;
+    UPDATE collections SET include_hidden = 1;
+    CREATE TABLE collection_ignores (
+        collection_id INTEGER NOT NULL,
+        pattern TEXT NOT NULL,
+        UNIQUE (collection_id, pattern),
+        FOREIGN KEY (collection_id) REFERENCES collections(id) ON DELETE CASCADE
+    );",
+    // 9: Details from the `.folder_finder.toml` file in the project folder
+    "ALTER TABLE projects ADD COLUMN display_name TEXT;
+    ALTER TABLE projects ADD COLUMN description TEXT;
+    CREATE TABLE project_aliases (
+        project_id INTEGER NOT NULL,
+        alias TEXT NOT NULL,
+        UNIQUE (project_id, alias),
+        FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+    );",
+    // 10: Every time a project is jumped to, for ranking by frecency
+    "CREATE TABLE visits (
+        project_id INTEGER NOT NULL,
+        visited_at TEXT NOT NULL,
+        FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE
+    );
+    CREATE INDEX visits_project ON visits (project_id);",
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The database was created by a newer version of folder_finder
+    TooNew {
+        found: i64,
+        supported: i64,
+    },
+    Sqlite(rusqlite::Error),
+}
+
+impl std::fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MigrationError::TooNew { found, supported } => write!(
+                f,
+                "The database has schema version {}, but this version of folder_finder only supports up to {}. Please update folder_finder",
+                found, supported
+            ),
+            MigrationError::Sqlite(e) => write!(f, "Failed to migrate the database: {}", e),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+/// The schema version this binary creates and understands
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+pub fn current_version(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+/// Bring the database up to the latest schema version, applying every missing step in order
+pub fn migrate(conn: &mut Connection) -> Result<(), MigrationError> {
+    let version = current_version(conn)?;
+    if version > latest_version() {
+        return Err(MigrationError::TooNew {
+            found: version,
+            supported: latest_version(),
+        });
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        // NOTE: Each step runs in its own transaction, so a failing step leaves the database at
+        // the last successful version
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(conn: &Connection, query: &str) -> Vec<(i64, Option<i64>)> {
+        let mut stmt = conn.prepare(query).unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    /// A database from before migrations existed: the initial tables at version 0, without
+    /// unique indexes or enforced foreign keys
+    fn unversioned_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(MIGRATIONS[0]).unwrap();
+        conn.execute_batch(
+            "INSERT INTO collections (id, path) VALUES (1, '/c'), (2, '/c');
+            INSERT INTO projects (id, name, path, collection_id)
+                VALUES (1, 'a', '/c/a', 2), (2, 'a', '/c/a', 1), (3, 'b', '/c/b', 9);
+            INSERT INTO tags (id, name) VALUES (1, 'rust'), (2, 'rust'), (3, 'cli');
+            INSERT INTO project_tags (data_id, tag_id)
+                VALUES (1, 1), (2, 2), (2, 3), (99, 1), (1, 42);
+            INSERT INTO collection_tags (data_id, tag_id) VALUES (2, 2), (1, 1), (7, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn merges_duplicates_and_removes_dangling_rows() {
+        let mut conn = unversioned_db();
+        migrate(&mut conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert_eq!(
+            rows(&conn, "SELECT id, NULL FROM collections ORDER BY id"),
+            [(1, None)]
+        );
+        assert_eq!(
+            rows(&conn, "SELECT id, collection_id FROM projects ORDER BY id"),
+            [(1, Some(1)), (3, None)]
+        );
+        assert_eq!(
+            rows(&conn, "SELECT id, NULL FROM tags ORDER BY id"),
+            [(1, None), (3, None)]
+        );
+        assert_eq!(
+            rows(
+                &conn,
+                "SELECT data_id, tag_id FROM project_tags ORDER BY data_id, tag_id"
+            ),
+            [(1, Some(1)), (1, Some(3))]
+        );
+        assert_eq!(
+            rows(&conn, "SELECT data_id, tag_id FROM collection_tags"),
+            [(1, Some(1))]
+        );
+    }
+
+    #[test]
+    fn refuses_newer_databases() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&mut conn),
+            Err(MigrationError::TooNew { .. })
+        ));
+    }
+}
@@ -0,0 +1,181 @@
+//! Output formats for the list commands, selected with the global `--format` option
+
+use crate::types::{Collection, Project, Tag, TagLink};
+use serde::Serialize;
+
+pub const FORMATS: [&str; 6] = ["table", "json", "jsonl", "csv", "tsv", "paths"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable, using the `Display` impls
+    Table,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    Csv,
+    Tsv,
+    /// Only the path (or name for tags) of each item, one per line
+    Paths,
+}
+
+impl Format {
+    pub fn from_args(args: &clap::ArgMatches) -> Format {
+        match args.get_one::<String>("format").map(|f| f.as_str()) {
+            None | Some("table") => Format::Table,
+            Some("json") => Format::Json,
+            Some("jsonl") => Format::Jsonl,
+            Some("csv") => Format::Csv,
+            Some("tsv") => Format::Tsv,
+            Some("paths") => Format::Paths,
+            Some(_) => panic!("Should be handled by clap itself"),
+        }
+    }
+}
+
+/// Something that can be printed by the list commands
+pub trait Row: Serialize + std::fmt::Display {
+    fn headers() -> Vec<&'static str>;
+    fn fields(&self) -> Vec<String>;
+    /// The value printed by `--format paths`
+    fn path(&self) -> &str;
+}
+
+/// Tags with their source, e.g. `git=auto:git,cli=manual`
+fn join_sources(links: &[TagLink]) -> String {
+    let mut sources = links
+        .iter()
+        .map(|link| format!("{}={}", link.tag.name, link.source))
+        .collect::<Vec<_>>();
+    sources.sort();
+    sources.join(",")
+}
+
+fn join_tags<'a>(tags: impl Iterator<Item = &'a Tag>) -> String {
+    let mut names = tags.map(|tag| tag.name.as_str()).collect::<Vec<_>>();
+    names.sort();
+    names.join(",")
+}
+
+fn id_field(id: Option<i64>) -> String {
+    id.map(|id| id.to_string()).unwrap_or_default()
+}
+
+impl Row for Project {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "id",
+            "name",
+            "path",
+            "collection",
+            "tags",
+            "tag_sources",
+            "remotes",
+        ]
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            id_field(self.id),
+            self.shown_name().to_string(),
+            self.path.clone(),
+            self.collection
+                .as_ref()
+                .map(|c| c.path.clone())
+                .unwrap_or_default(),
+            join_tags(self.tags.iter()),
+            join_sources(&self.tag_links),
+            self.remotes
+                .iter()
+                .map(|remote| remote.url.as_str())
+                .collect::<Vec<_>>()
+                .join(","),
+        ]
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Row for Collection {
+    fn headers() -> Vec<&'static str> {
+        vec!["id", "path", "tags", "tag_sources"]
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![
+            id_field(self.id),
+            self.path.clone(),
+            join_tags(self.tags.iter()),
+            join_sources(&self.tag_links),
+        ]
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Row for Tag {
+    fn headers() -> Vec<&'static str> {
+        vec!["name"]
+    }
+
+    fn fields(&self) -> Vec<String> {
+        vec![self.name.clone()]
+    }
+
+    fn path(&self) -> &str {
+        &self.name
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn tsv_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+pub fn print<T: Row>(items: &[T], format: Format) {
+    match format {
+        Format::Table => items.iter().for_each(|item| println!("{}", item)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(items).unwrap()),
+        Format::Jsonl => items
+            .iter()
+            .for_each(|item| println!("{}", serde_json::to_string(item).unwrap())),
+        Format::Csv => {
+            println!("{}", T::headers().join(","));
+            items.iter().for_each(|item| {
+                let fields = item
+                    .fields()
+                    .iter()
+                    .map(|f| csv_field(f))
+                    .collect::<Vec<_>>();
+                println!("{}", fields.join(","));
+            });
+        }
+        Format::Tsv => {
+            println!("{}", T::headers().join("\t"));
+            items.iter().for_each(|item| {
+                let fields = item
+                    .fields()
+                    .iter()
+                    .map(|f| tsv_field(f))
+                    .collect::<Vec<_>>();
+                println!("{}", fields.join("\t"));
+            });
+        }
+        Format::Paths => items.iter().for_each(|item| println!("{}", item.path())),
+    }
+}
@@ -0,0 +1,79 @@
+//! Interactive selection of a single item, used by the `goto` commands.
+//!
+//! The built-in picker uses dialoguer's fuzzy select. Setting `picker = "fzf"` in the config file
+//! uses an external `fzf` binary instead, falling back to the built-in picker if it can't be
+//! started.
+
+use crate::config;
+use crate::error::{Error, Result};
+use fzf_wrapped::Fzf;
+use serde::Deserialize;
+use std::io::IsTerminal;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Builtin,
+    Fzf,
+}
+
+/// Let the user pick one of the lines, returning its index. `None` if the selection was cancelled
+pub fn pick(prompt: &str, lines: &[String]) -> Result<Option<usize>> {
+    if lines.is_empty() {
+        return Ok(None);
+    }
+    match config::load()?.picker {
+        Backend::Builtin => pick_builtin(prompt, lines),
+        Backend::Fzf => match pick_fzf(prompt, lines) {
+            Some(index) => Ok(index),
+            None => {
+                eprintln!("Failed to start fzf, using the built-in picker");
+                pick_builtin(prompt, lines)
+            }
+        },
+    }
+}
+
+fn pick_builtin(prompt: &str, lines: &[String]) -> Result<Option<usize>> {
+    // NOTE: dialoguer keeps polling for keys when stdin isn't a terminal
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Prompt(dialoguer::Error::IO(std::io::Error::other(
+            "not a terminal",
+        ))));
+    }
+    // NOTE: dialoguer draws on stderr, so stdout only contains the result for shell wrappers
+    Ok(dialoguer::FuzzySelect::new()
+        .with_prompt(prompt)
+        .items(lines)
+        .default(0)
+        .interact_opt()?)
+}
+
+/// Returns `None` if fzf couldn't be run, `Some(None)` if the user cancelled
+fn pick_fzf(prompt: &str, lines: &[String]) -> Option<Option<usize>> {
+    // NOTE: Each line is prefixed with its index, hidden from the user, so lines that look the
+    // same can still be told apart
+    let mut fzf = Fzf::builder()
+        .custom_args([
+            "--delimiter=\t".to_string(),
+            "--with-nth=2..".to_string(),
+            format!("--prompt={}> ", prompt),
+        ])
+        .build()
+        .ok()?;
+    fzf.run().ok()?;
+    fzf.add_items(
+        lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| format!("{}\t{}", i, line)),
+    )
+    .ok()?;
+    let selection = fzf.output().unwrap_or_default();
+    Some(
+        selection
+            .split_once('\t')
+            .and_then(|(index, _)| index.parse().ok()),
+    )
+}
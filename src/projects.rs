@@ -1,17 +1,47 @@
 use clap::{arg, value_parser, Command};
-use fzf_wrapped::Fzf;
 
 use crate::auto_tags;
+use crate::completions;
 use crate::database;
+use crate::detectors;
+use crate::error::{Error, Result};
+use crate::folder_file;
+use crate::git;
+use crate::jump;
+use crate::output;
+use crate::picker;
+use crate::query;
 use crate::tags;
 use crate::types;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub fn command() -> Command {
     Command::new("project")
         .about("Manage projects")
         .arg_required_else_help(true)
-        .subcommand(Command::new("list").about("List all projects"))
+        .subcommand(Command::new("list").about(
+            "List all projects. Auto-detected tags are marked with *, inherited ones with ^",
+        ))
+        .subcommand(
+            Command::new("search")
+                .about("Search projects by tags, e.g. 'rust & !archived & (cli | tui)'")
+                .arg(
+                    arg!([QUERY])
+                        .required_unless_present("remote")
+                        .num_args(1..),
+                )
+                .arg(
+                    arg!(-r --remote <REMOTE> "Only projects with a git remote matching e.g. 'owner/repo'"),
+                ),
+        )
+        .subcommand(
+            Command::new("show").about("Show the details of a project").arg(
+                arg!(<PATH>)
+                    .required(true)
+                    .value_parser(value_parser!(PathBuf))
+                    .add(completions::project_paths()),
+            ),
+        )
         .subcommand(
             Command::new("add").about("Add a new project").arg(
                 arg!(<PATH>)
@@ -23,7 +53,8 @@ pub fn command() -> Command {
             Command::new("del").about("Delete a project").arg(
                 arg!(<PATH>)
                     .required(true)
-                    .value_parser(value_parser!(PathBuf)),
+                    .value_parser(value_parser!(PathBuf))
+                    .add(completions::project_paths()),
             ),
         )
         .subcommand(
@@ -32,23 +63,101 @@ pub fn command() -> Command {
                 .arg(
                     arg!(<PATH>)
                         .required(true)
-                        .value_parser(value_parser!(PathBuf)),
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::project_paths()),
                 )
                 .arg(arg!(-f --force "Add tags if they don't exist'"))
-                .arg(arg!(<TAG>).required(true).num_args(1..)),
+                .arg(
+                    arg!(-w --"write-file")
+                        .help("Also add the tags to the .folder_finder.toml file in the project"),
+                )
+                .arg(arg!(<TAG>)
+                        .required(true)
+                        .num_args(1..)
+                        .add(completions::tag_names())),
+        )
+        .subcommand(
+            Command::new("retag")
+                .about("Re-run auto-tagging and re-read .folder_finder.toml files, adding new tags and removing stale ones")
+                .arg(
+                    arg!([PATH])
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::project_paths())
+                        .conflicts_with_all(["all", "collection", "tag"]),
+                )
+                .arg(arg!(-a --all "Retag all projects"))
+                .arg(
+                    arg!(-c --collection <COLLECTION> "Retag all projects in a collection")
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::collection_paths()),
+                )
+                .arg(arg!(-t --tag <TAG> "Retag all projects with a tag").add(completions::tag_names()))
+                .group(
+                    clap::ArgGroup::new("target")
+                        .args(["PATH", "all", "collection", "tag"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("untag")
+                .about("Remove tags from a project")
+                .arg(
+                    arg!(<PATH>)
+                        .required(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .add(completions::project_paths()),
+                )
+                .arg(
+                    arg!([TAG])
+                        .num_args(1..)
+                        .add(completions::tag_names()),
+                ),
+        )
+        .subcommand(
+            Command::new("goto").about("Go to a project, most visited first").arg(
+                arg!(--"print-path" "Only print the selected path, for use in shell wrappers"),
+            ),
         )
-        .subcommand(Command::new("goto").about("Go to a project"))
 }
 
-pub fn run(args: &clap::ArgMatches) {
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
     match args.subcommand() {
-        Some(("list", _)) => list(),
-        Some(("goto", _)) => goto(),
+        Some(("list", args)) => list(output::Format::from_args(args)),
+        Some(("search", args)) => search(
+            args.get_many::<String>("QUERY")
+                .unwrap_or_default()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+            args.get_one::<String>("remote").cloned(),
+            output::Format::from_args(args),
+        ),
+        Some(("show", args)) => show(args.get_one::<PathBuf>("PATH").unwrap().clone()),
+        Some(("goto", args)) => goto(args.get_flag("print-path")),
         Some(("add", args)) => add(args.get_one::<PathBuf>("PATH").unwrap().clone(), true),
         Some(("del", args)) => del(args.get_one::<PathBuf>("PATH").unwrap().clone()),
-        Some(("tag", args)) => add_tag(
+        Some(("tag", args)) => {
+            let path = args.get_one::<PathBuf>("PATH").unwrap().clone();
+            let tags = args
+                .get_many::<String>("TAG")
+                .unwrap_or_default()
+                .map(|name| types::Tag {
+                    name: name.to_string(),
+                })
+                .collect::<Vec<_>>();
+            add_tag(path.clone(), args.get_flag("force"), tags.clone())?;
+            if args.get_flag("write-file") {
+                write_file(path, &tags)?;
+            }
+            Ok(())
+        }
+        Some(("retag", args)) => retag(
+            args.get_one::<PathBuf>("PATH").cloned(),
+            args.get_one::<PathBuf>("collection").cloned(),
+            args.get_one::<String>("tag").cloned(),
+        ),
+        Some(("untag", args)) => remove_tag(
             args.get_one::<PathBuf>("PATH").unwrap().clone(),
-            args.get_flag("force"),
             args.get_many::<String>("TAG")
                 .unwrap_or_default()
                 .map(|name| types::Tag {
@@ -60,83 +169,293 @@ pub fn run(args: &clap::ArgMatches) {
     }
 }
 
-pub fn from_path(path: &PathBuf) -> types::DataType {
-    let full_path = path.canonicalize().unwrap();
+/// The absolute path of a folder as it is stored in the database, for looking it up. Falls back
+/// to the path made absolute if the folder is gone, so its entry can still be removed
+pub fn full_path(path: &Path) -> Result<String> {
+    let full_path = path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .map_err(|source| Error::InvalidPath {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    to_string(full_path)
+}
+
+/// Same as `full_path`, but the folder must exist. Used when adding it to the database
+pub fn existing_path(path: &Path) -> Result<String> {
+    let full_path = path.canonicalize().map_err(|source| Error::InvalidPath {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    to_string(full_path)
+}
+
+fn to_string(path: PathBuf) -> Result<String> {
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => Err(Error::NonUtf8Path(path)),
+    }
+}
+
+pub fn from_path(path: &Path) -> Result<types::DataType> {
+    Ok(project_at(full_path(path)?))
+}
+
+fn project_at(full_path: String) -> types::DataType {
+    let name = match Path::new(&full_path).file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => full_path.clone(),
+    };
     let proj = types::Project {
-        name: full_path.file_name().unwrap().to_str().unwrap().to_string(),
-        path: full_path.to_str().unwrap().to_string(),
+        id: None,
+        name,
+        display_name: None,
+        description: None,
+        aliases: Vec::new(),
+        path: full_path,
         collection: None,
         tags: Default::default(),
+        tag_links: Vec::new(),
+        remotes: Vec::new(),
+        languages: Vec::new(),
     };
     types::DataType::Project(proj)
 }
 
-fn list() {
-    let projects = database::list_projects().unwrap();
-    projects.iter().for_each(|proj| println!("{}", proj));
+fn list(format: output::Format) -> Result<()> {
+    let projects = database::store()?.list_projects()?;
+    output::print(&projects, format);
+    Ok(())
+}
+
+fn search(query: String, remote: Option<String>, format: output::Format) -> Result<()> {
+    let expr = if query.trim().is_empty() {
+        None
+    } else {
+        Some(query::parse(&query)?)
+    };
+    let remote = remote.map(|r| r.to_lowercase());
+    let projects = database::store()?
+        .list_projects()?
+        .into_iter()
+        .filter(|proj| expr.as_ref().is_none_or(|expr| expr.matches(&proj.tags)))
+        .filter(|proj| {
+            remote.as_ref().is_none_or(|pattern| {
+                proj.remotes.iter().any(|r| {
+                    git::RemoteUrl::parse(&r.url)
+                        .is_some_and(|url| url.to_string().to_lowercase().contains(pattern))
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+    output::print(&projects, format);
+    Ok(())
 }
 
-fn goto() {
-    let projects = database::list_projects().unwrap();
-    let strings: Vec<String> = projects.iter().map(|proj| format!("{}", proj)).collect();
-    let mut fzf = Fzf::default();
-    fzf.run().expect("Failed to start fzf");
-    fzf.add_items(&strings).expect("Failed to add items");
-    let users_selection = fzf.output().expect("Failed to get the user's output");
-    let index = strings.iter().position(|x| x == &users_selection).unwrap();
+fn show(path: PathBuf) -> Result<()> {
+    let full_path = full_path(&path)?;
+    let projects = database::store()?.list_projects()?;
+    let Some(proj) = projects.iter().find(|proj| proj.path == full_path) else {
+        return Err(Error::NotFound {
+            kind: types::EmptyDataType::Project,
+            key: full_path,
+        });
+    };
+
+    println!("Name:       {}", proj.shown_name());
+    if let Some(description) = &proj.description {
+        println!("About:      {}", description);
+    }
+    if !proj.aliases.is_empty() {
+        println!("Aliases:    {}", proj.aliases.join(", "));
+    }
+    println!("Path:       {}", proj.path);
+    if let Some(collection) = &proj.collection {
+        println!("Collection: {}", collection.path);
+    }
+    println!("Tags:");
+    let mut links = proj.tag_links.iter().collect::<Vec<_>>();
+    links.sort_by(|a, b| a.tag.name.cmp(&b.tag.name));
+    links.iter().for_each(|link| {
+        let line = format!(
+            "  {:<20} {:<30} {}",
+            link.tag.name,
+            link.source.to_string(),
+            link.added_at.as_deref().unwrap_or_default()
+        );
+        println!("{}", line.trim_end())
+    });
+    if !proj.languages.is_empty() {
+        let breakdown = proj
+            .languages
+            .iter()
+            .map(|l| format!("{} {:.1}%", l.language, l.share * 100.0))
+            .collect::<Vec<_>>();
+        println!("Languages:  {}", breakdown.join(", "));
+    }
+    if !proj.remotes.is_empty() {
+        println!("Remotes:");
+        proj.remotes
+            .iter()
+            .for_each(|remote| println!("  {:<10} {}", remote.name, remote.url));
+    }
+    Ok(())
+}
+
+fn goto(print_path: bool) -> Result<()> {
+    let store = database::store()?;
+    let mut projects = store.list_projects()?;
+    jump::sort_by_frecency(&mut projects)?;
+    // NOTE: With the path, projects in folders with the same name can be told apart
+    let lines: Vec<String> = projects
+        .iter()
+        .map(|proj| format!("{} | {}", proj, proj.path))
+        .collect();
+    let index = picker::pick("Project", &lines)?.ok_or(Error::Cancelled)?;
     let proj = &projects[index];
-    std::env::set_current_dir(&proj.path).unwrap();
     println!("{}", proj.path);
+    // NOTE: The path is already printed, a failed visit shouldn't keep the shell wrapper from
+    // changing into it
+    let _ = store.record_visit(&types::DataType::Project(proj.clone()));
+    if !print_path {
+        // NOTE: A child process can't change the directory of its parent shell
+        eprintln!("Use the `ff` function from `shell-init` to change into the project directory");
+    }
+    Ok(())
 }
 
-pub fn add(path: PathBuf, ask_tags: bool) {
-    let proj = from_path(&path);
-    let res = database::add(&proj);
-    match res {
-        Ok(_) => println!("Project added"),
-        Err(_e) => {
-            eprintln!("The project already exists");
-            return;
-        }
-    }
+pub fn add(path: PathBuf, ask_tags: bool) -> Result<()> {
+    let proj = project_at(existing_path(&path)?);
+    database::store()?.add(&proj)?;
+    println!("Project added");
+    folder_file::import(&proj)?;
+    auto_tags::auto_tag_project(&proj)?;
+
+    // NOTE: Asked last, so the project is complete even if the prompt fails
     if ask_tags {
-        add_tag(path.clone(), true, tags::select_tags(true));
+        let tags = tags::select_tags(true)?;
+        if !tags.is_empty() {
+            add_tag(path, true, tags)?;
+        }
     }
-
-    auto_tags::auto_tag_project(&proj);
+    Ok(())
 }
 
-fn del(path: PathBuf) {
-    let proj = from_path(&path);
-    let res = database::delete(&proj);
-    match res {
-        Ok(_) => println!("Deleted"),
-        Err(_e) => {
-            eprintln!("Error deleting collection");
-            return;
+/// Retag a single project, all projects in a collection, all projects with a tag, or all projects
+/// if none of these are given (`--all`)
+fn retag(path: Option<PathBuf>, collection: Option<PathBuf>, tag: Option<String>) -> Result<()> {
+    let projects = database::store()?.list_projects()?;
+    let selected = if let Some(path) = path {
+        let full_path = full_path(&path)?;
+        let selected = projects
+            .into_iter()
+            .filter(|proj| proj.path == full_path)
+            .collect::<Vec<_>>();
+        if selected.is_empty() {
+            return Err(Error::NotFound {
+                kind: types::EmptyDataType::Project,
+                key: full_path,
+            });
         }
+        selected
+    } else if let Some(collection) = collection {
+        let collection_path = full_path(&collection)?;
+        projects
+            .into_iter()
+            .filter(|proj| {
+                proj.collection
+                    .as_ref()
+                    .is_some_and(|c| c.path == collection_path)
+            })
+            .collect()
+    } else if let Some(tag) = tag {
+        // NOTE: Tags are stored as typed, so compare case-insensitively like queries do
+        let tag = tag.to_lowercase();
+        projects
+            .into_iter()
+            .filter(|proj| proj.tags.iter().any(|t| t.name.to_lowercase() == tag))
+            .collect()
+    } else {
+        projects
+    };
+    if selected.is_empty() {
+        return Err(Error::NoMatch);
     }
+
+    let registry = detectors::Registry::load()?;
+    let mut unchanged = 0;
+    for proj in selected {
+        if !Path::new(&proj.path).is_dir() {
+            eprintln!("Skipping {}, the folder is missing", proj.path);
+            continue;
+        }
+        let data = types::DataType::Project(proj.clone());
+        let mut diff = folder_file::import(&data)?;
+        let detected = auto_tags::auto_tag_with(&registry, &data)?;
+        diff.added.extend(detected.added);
+        diff.removed.extend(detected.removed);
+        if diff.is_empty() {
+            unchanged += 1;
+            continue;
+        }
+        println!("{}", proj.path);
+        diff.added
+            .iter()
+            .for_each(|d| println!("  + {} ({})", d.tag.name, d.evidence));
+        diff.removed
+            .iter()
+            .for_each(|tag| println!("  - {}", tag.name));
+    }
+    println!("{} project(s) unchanged", unchanged);
+    Ok(())
+}
+
+fn del(path: PathBuf) -> Result<()> {
+    let proj = from_path(&path)?;
+    database::store()?.delete(&proj)?;
+    println!("Deleted");
+    Ok(())
 }
 
-fn add_tag(path: PathBuf, force: bool, tags: Vec<types::Tag>) {
+fn add_tag(path: PathBuf, force: bool, tags: Vec<types::Tag>) -> Result<()> {
+    let proj = from_path(&path)?;
     let tags = if tags.is_empty() {
-        tags::select_tags(force)
+        tags::select_tags(force)?
     } else {
         tags
     };
 
-    tags.iter().for_each(|tag| {
-        let proj = from_path(&path);
-        let res = database::add_tag(&proj, tag.clone(), force);
-        match res {
-            Ok(_) => println!("Tag added: {}", tag.name),
-            Err(e) => {
-                match e {
-                    types::NotFoundError::Tag => eprintln!("Tag not found"),
-                    _ => eprintln!("Not currently in folder with project"),
-                }
-                return;
-            }
+    for tag in tags {
+        database::store()?.add_tag(&proj, tag.clone(), force, &types::TagSource::Manual)?;
+        println!("Tag added: {}", tag.name);
+    }
+    Ok(())
+}
+
+fn write_file(path: PathBuf, tags: &[types::Tag]) -> Result<()> {
+    let full_path = existing_path(&path)?;
+    folder_file::write_tags(Path::new(&full_path), tags)?;
+    println!("Tags written to {}", folder_file::FILE_NAME);
+    Ok(())
+}
+
+fn remove_tag(path: PathBuf, tags: Vec<types::Tag>) -> Result<()> {
+    let proj = from_path(&path)?;
+    let tags = if tags.is_empty() {
+        let current = database::store()?.get_direct_tags(&proj)?;
+        if current.is_empty() {
+            println!("The project has no tags to remove");
+            return Ok(());
         }
-    });
+        tags::select_from(current.into_iter().collect())?
+    } else {
+        tags
+    };
+
+    for tag in tags {
+        database::store()?.remove_tag(&proj, tag.clone())?;
+        println!("Tag removed: {}", tag.name);
+    }
+    Ok(())
 }
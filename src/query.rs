@@ -0,0 +1,226 @@
+//! Boolean tag queries, e.g. `rust & !archived & (cli | tui)`
+//!
+//! Operators, from loosest to tightest binding: `|` (or), `&` (and), `!` (not).
+//! Tags next to each other without an operator are combined with `&`.
+
+use crate::types::Tag;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Tag(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn matches(&self, tags: &HashSet<Tag>) -> bool {
+        match self {
+            Expr::Tag(name) => tags.iter().any(|tag| tag.name.to_lowercase() == *name),
+            Expr::Not(expr) => !expr.matches(tags),
+            Expr::And(lhs, rhs) => lhs.matches(tags) && rhs.matches(tags),
+            Expr::Or(lhs, rhs) => lhs.matches(tags) || rhs.matches(tags),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid query: {}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Tag(String),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Tag(name) => write!(f, "tag '{}'", name),
+            Token::And => write!(f, "'&'"),
+            Token::Or => write!(f, "'|'"),
+            Token::Not => write!(f, "'!'"),
+            Token::Open => write!(f, "'('"),
+            Token::Close => write!(f, "')'"),
+        }
+    }
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => tokens.push(Token::And),
+            '|' => tokens.push(Token::Or),
+            '!' => tokens.push(Token::Not),
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            c if c.is_whitespace() => {}
+            c => {
+                let mut name = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "&|!()".contains(next) {
+                        break;
+                    }
+                    name.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Tag(name.to_lowercase()));
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, ParseError> {
+        let mut expr = self.unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                // NOTE: Juxtaposed terms are an implicit and
+                Some(Token::Tag(_)) | Some(Token::Not) | Some(Token::Open) => {}
+                _ => break,
+            }
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, ParseError> {
+        match self.next() {
+            Some(Token::Tag(name)) => Ok(Expr::Tag(name)),
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(ParseError {
+                        message: "missing closing ')'".to_string(),
+                    }),
+                }
+            }
+            Some(token) => Err(ParseError {
+                message: format!("unexpected {}", token),
+            }),
+            None => Err(ParseError {
+                message: "unexpected end of query".to_string(),
+            }),
+        }
+    }
+}
+
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(input),
+        pos: 0,
+    };
+    let expr = parser.or()?;
+    match parser.next() {
+        None => Ok(expr),
+        Some(token) => Err(ParseError {
+            message: format!("unexpected {}", token),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(name: &str) -> Box<Expr> {
+        Box::new(Expr::Tag(name.to_string()))
+    }
+
+    fn tags(names: &[&str]) -> HashSet<Tag> {
+        names
+            .iter()
+            .map(|name| Tag {
+                name: name.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(
+            parse("a | b & !c").unwrap(),
+            Expr::Or(
+                tag("a"),
+                Box::new(Expr::And(tag("b"), Box::new(Expr::Not(tag("c")))))
+            ),
+        );
+        assert_eq!(
+            parse("(a | b) c").unwrap(),
+            Expr::And(Box::new(Expr::Or(tag("a"), tag("b"))), tag("c")),
+        );
+    }
+
+    #[test]
+    fn matches_case_insensitively() {
+        let expr = parse("Rust & !archived").unwrap();
+        assert!(expr.matches(&tags(&["rust", "cli"])));
+        assert!(expr.matches(&tags(&["RUST"])));
+        assert!(!expr.matches(&tags(&["rust", "archived"])));
+    }
+
+    #[test]
+    fn errors_name_the_token() {
+        assert_eq!(
+            parse("a )").unwrap_err().to_string(),
+            "Invalid query: unexpected ')'"
+        );
+        assert_eq!(
+            parse("& a").unwrap_err().to_string(),
+            "Invalid query: unexpected '&'"
+        );
+        assert_eq!(
+            parse("(a | b").unwrap_err().to_string(),
+            "Invalid query: missing closing ')'"
+        );
+        assert_eq!(
+            parse("a |").unwrap_err().to_string(),
+            "Invalid query: unexpected end of query"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use clap::{arg, Command};
+
+const BASH: &str = r#"ff() {
+    # Find the subcommand, skipping global options such as `--profile work` in front of it
+    local arg cmd="" sub="" skip=""
+    for arg in "$@"; do
+        if [ -n "$skip" ]; then
+            skip=""
+            continue
+        fi
+        case "$arg" in
+            --db|--profile|--format) skip=1 ;;
+            -*) ;;
+            *)
+                if [ -z "$cmd" ]; then
+                    cmd="$arg"
+                else
+                    sub="$arg"
+                    break
+                fi
+                ;;
+        esac
+    done
+    if [ "$cmd" = "jump" ] && ! printf '%s\n' "$@" | grep -qxE -- '-a|--all'; then
+        local dir
+        dir="$(command {bin} "$@")" && [ -n "$dir" ] && cd -- "$dir"
+    elif [ "$sub" = "goto" ] && { [ "$cmd" = "project" ] || [ "$cmd" = "collection" ]; }; then
+        local dir
+        dir="$(command {bin} "$@" --print-path)" && [ -n "$dir" ] && cd -- "$dir"
+    else
+        command {bin} "$@"
+    fi
+}
+
+fcd() {
+    ff project goto "$@"
+}
+"#;
+
+const FISH: &str = r#"function ff --wraps {bin}
+    # Find the subcommand, skipping global options such as `--profile work` in front of it
+    set -l words
+    set -l skip 0
+    for arg in $argv
+        if test $skip = 1
+            set skip 0
+        else if contains -- $arg --db --profile --format
+            set skip 1
+        else if not string match -q -- '-*' $arg
+            set -a words $arg
+        end
+    end
+    if test "$words[1]" = jump; and not contains -- --all $argv; and not contains -- -a $argv
+        set -l dir (command {bin} $argv)
+        and test -n "$dir"
+        and cd $dir
+    else if test (count $words) -ge 2; and contains -- $words[1] project collection; and test "$words[2]" = goto
+        set -l dir (command {bin} $argv --print-path)
+        and test -n "$dir"
+        and cd $dir
+    else
+        command {bin} $argv
+    end
+end
+
+function fcd
+    ff project goto $argv
+end
+"#;
+
+const NUSHELL: &str = r#"def --env --wrapped ff [...args] {
+    # Find the subcommand, skipping global options such as `--profile work` in front of it
+    mut words = []
+    mut skip = false
+    for arg in $args {
+        if $skip {
+            $skip = false
+        } else if $arg in ["--db" "--profile" "--format"] {
+            $skip = true
+        } else if not ($arg | str starts-with "-") {
+            $words = ($words | append $arg)
+        }
+    }
+    let cmd = ($words.0? | default "")
+    let sub = ($words.1? | default "")
+    if $cmd == "jump" and not ("--all" in $args or "-a" in $args) {
+        let dir = (^{bin} ...$args | str trim)
+        if ($dir | is-not-empty) { cd $dir }
+    } else if ($cmd in [project collection]) and $sub == "goto" {
+        let dir = (^{bin} ...$args --print-path | str trim)
+        if ($dir | is-not-empty) { cd $dir }
+    } else {
+        ^{bin} ...$args
+    }
+}
+
+def --env --wrapped fcd [...args] {
+    ff project goto ...$args
+}
+"#;
+
+pub fn command() -> Command {
+    Command::new("shell-init")
+        .about("Print a shell function that changes directory on goto")
+        .long_about(
+            "Print a shell function that changes directory on goto.\n\
+            Add the output to your shell config, e.g. `eval \"$(folder_finder shell-init bash)\"`.\n\
+            This defines `ff`, which wraps folder_finder and cd's into the result of `jump`, \
+            `project goto` and `collection goto`, and `fcd` as a shortcut for `ff project goto`.",
+        )
+        .arg(
+            arg!(<SHELL>)
+                .required(true)
+                .value_parser(["bash", "zsh", "fish", "nushell"]),
+        )
+}
+
+pub fn run(args: &clap::ArgMatches) {
+    let script = match args.get_one::<String>("SHELL").unwrap().as_str() {
+        // NOTE: zsh understands the same function syntax as bash
+        "bash" | "zsh" => BASH,
+        "fish" => FISH,
+        "nushell" => NUSHELL,
+        _ => panic!("Should be handled by clap itself"),
+    };
+    print!("{}", script.replace("{bin}", env!("CARGO_PKG_NAME")));
+}
@@ -1,4 +1,7 @@
+use crate::completions;
 use crate::database;
+use crate::error::{Error, Result};
+use crate::output;
 use crate::types;
 
 use clap::{arg, Command};
@@ -14,15 +17,40 @@ pub fn command() -> Command {
                 .arg(arg!(<TAG>).required(true).num_args(1..)),
         )
         .subcommand(
-            Command::new("del")
-                .about("Delete a tag")
-                .arg(arg!(<TAG>).required(true).num_args(1..)),
+            Command::new("del").about("Delete a tag").arg(
+                arg!(<TAG>)
+                    .required(true)
+                    .num_args(1..)
+                    .add(completions::tag_names()),
+            ),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Rename a tag, use `merge` if the new name is already in use")
+                .long_about(
+                    "Rename a tag, use `merge` if the new name is already in use.\n\
+                    Auto-detected links become manual, so retagging doesn't undo the rename. \
+                    The detector may still add the old name again.",
+                )
+                .arg(arg!(<OLD>).required(true).add(completions::tag_names()))
+                .arg(arg!(<NEW>).required(true)),
+        )
+        .subcommand(
+            Command::new("merge")
+                .about("Merge tags into another tag, e.g. 'merge js javascript'")
+                .arg(
+                    arg!(<SRC>)
+                        .required(true)
+                        .num_args(1..)
+                        .add(completions::tag_names()),
+                )
+                .arg(arg!(<DEST>).required(true).add(completions::tag_names())),
         )
 }
 
-pub fn run(args: &clap::ArgMatches) {
+pub fn run(args: &clap::ArgMatches) -> Result<()> {
     match args.subcommand() {
-        Some(("list", _)) => list(),
+        Some(("list", args)) => list(output::Format::from_args(args)),
         Some(("add", args)) => add(args
             .get_many::<String>("TAG")
             .unwrap_or_default()
@@ -33,6 +61,17 @@ pub fn run(args: &clap::ArgMatches) {
             .unwrap_or_default()
             .map(|v| v.as_str())
             .collect()),
+        Some(("rename", args)) => rename(
+            args.get_one::<String>("OLD").unwrap(),
+            args.get_one::<String>("NEW").unwrap(),
+        ),
+        Some(("merge", args)) => merge(
+            args.get_many::<String>("SRC")
+                .unwrap_or_default()
+                .map(|v| v.as_str())
+                .collect(),
+            args.get_one::<String>("DEST").unwrap(),
+        ),
         _ => panic!("Should be handled by clap itself"),
     }
 }
@@ -44,58 +83,83 @@ fn word_to_tag(word: &str) -> types::DataType {
     types::DataType::Tag(tag)
 }
 
-fn list() {
-    let tags = database::list_tags();
-    tags.iter().for_each(|tag| println!("{}", tag.name));
+fn list(format: output::Format) -> Result<()> {
+    let tags = database::store()?.list_tags()?;
+    output::print(&tags, format);
+    Ok(())
 }
 
-fn add(words: Vec<&str>) {
+fn add(words: Vec<&str>) -> Result<()> {
     for word in words {
         let tag = word_to_tag(word);
-        let res = database::add(&tag);
-        match res {
-            Ok(_) => println!("Tag added"),
-            Err(_e) => {
-                eprintln!("The tag already exists");
-                return;
-            }
-        }
+        database::store()?.add(&tag)?;
+        println!("Tag added");
     }
+    Ok(())
 }
 
-fn del(words: Vec<&str>) {
+fn del(words: Vec<&str>) -> Result<()> {
     for word in words {
         let tag = word_to_tag(word);
-        let res = database::delete(&tag);
-        match res {
-            Ok(_) => println!("Tag deleted"),
-            Err(_e) => {
-                eprintln!("Error deleting tag");
-                return;
-            }
-        }
+        database::store()?.delete(&tag)?;
+        println!("Tag deleted");
     }
+    Ok(())
 }
 
-fn query_new() -> Vec<types::Tag> {
+fn print_changes(changes: &types::TagChanges) {
+    println!(
+        "Updated {} project(s) and {} collection(s)",
+        changes.projects, changes.collections
+    );
+}
+
+fn rename(old: &str, new: &str) -> Result<()> {
+    let old = word_to_tag(old);
+    let new = word_to_tag(new);
+    let store = database::store()?;
+    if store.list_tags()?.contains(new.tag().unwrap()) {
+        return Err(Error::AlreadyExists {
+            kind: new.kind(),
+            key: new.tag().unwrap().name.clone(),
+        });
+    }
+    let changes = store.rename_tag(old.tag().unwrap(), new.tag().unwrap())?;
+    println!("Tag renamed");
+    print_changes(&changes);
+    Ok(())
+}
+
+fn merge(sources: Vec<&str>, dest: &str) -> Result<()> {
+    let sources = sources
+        .into_iter()
+        .map(|word| word_to_tag(word).tag().unwrap().clone())
+        .collect::<Vec<_>>();
+    let dest = word_to_tag(dest);
+    let changes = database::store()?.merge_tags(&sources, dest.tag().unwrap())?;
+    println!("Tags merged");
+    print_changes(&changes);
+    Ok(())
+}
+
+fn query_new() -> Result<Vec<types::Tag>> {
     let mut tags = Vec::new();
     loop {
         let tag = dialoguer::Input::<String>::new()
             .with_prompt("Enter a tag")
             .allow_empty(true)
-            .interact()
-            .unwrap();
+            .interact()?;
         if tag.is_empty() {
-            break tags;
+            break Ok(tags);
         } else {
             tags.push(types::Tag { name: tag });
         }
     }
 }
 
-pub fn select_tags(allow_new: bool) -> Vec<types::Tag> {
+pub fn select_tags(allow_new: bool) -> Result<Vec<types::Tag>> {
     //Option<types::Tag> {
-    let tags = database::list_tags();
+    let tags = database::store()?.list_tags()?;
     let mut tag_names = tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>();
     let mut index_offset = 0;
     if allow_new {
@@ -103,10 +167,7 @@ pub fn select_tags(allow_new: bool) -> Vec<types::Tag> {
         index_offset = 1;
     }
 
-    let selected = dialoguer::MultiSelect::new()
-        .items(&tag_names)
-        .interact()
-        .unwrap();
+    let selected = dialoguer::MultiSelect::new().items(&tag_names).interact()?;
 
     let query = selected.contains(&0);
     let mut selected_tags = selected
@@ -116,9 +177,20 @@ pub fn select_tags(allow_new: bool) -> Vec<types::Tag> {
         .collect::<Vec<_>>();
 
     if query {
-        query_new()
+        query_new()?
             .iter()
             .for_each(|tag| selected_tags.push(tag.clone()));
     }
-    selected_tags
+    Ok(selected_tags)
+}
+
+/// Let the user pick some of the given tags, e.g. to remove them from a project
+pub fn select_from(tags: Vec<types::Tag>) -> Result<Vec<types::Tag>> {
+    let mut tags = tags;
+    tags.sort_by(|a, b| a.name.cmp(&b.name));
+    let tag_names = tags.iter().map(|tag| tag.name.clone()).collect::<Vec<_>>();
+
+    let selected = dialoguer::MultiSelect::new().items(&tag_names).interact()?;
+
+    Ok(selected.iter().map(|&i| tags[i].clone()).collect())
 }
@@ -1,52 +1,261 @@
+use serde::{Serialize, Serializer};
 use std::collections::HashSet;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+#[serde(transparent)]
 pub struct Tag {
     pub name: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+impl std::fmt::Display for Tag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Where the link between a tag and a project or collection came from
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TagSource {
+    /// Added by hand
+    Manual,
+    /// Detected by the auto-tag rule with this name
+    Auto(String),
+    /// Inherited from the collection at this path, never stored for the project itself
+    Inherited(String),
+    /// Read from a file instead of added by hand
+    Imported,
+}
+
+impl TagSource {
+    pub fn parse(source: &str) -> TagSource {
+        if let Some(rule) = source.strip_prefix("auto:") {
+            TagSource::Auto(rule.to_string())
+        } else if let Some(path) = source.strip_prefix("inherited:") {
+            TagSource::Inherited(path.to_string())
+        } else if source == "imported" {
+            TagSource::Imported
+        } else {
+            TagSource::Manual
+        }
+    }
+
+    pub fn is_auto(&self) -> bool {
+        matches!(self, TagSource::Auto(_))
+    }
+
+    /// How strong the source is when a tag is linked in multiple ways, the same order `add_tag`
+    /// uses: manual over imported over auto, with inherited links last
+    fn rank(&self) -> u8 {
+        match self {
+            TagSource::Manual => 3,
+            TagSource::Imported => 2,
+            TagSource::Auto(_) => 1,
+            TagSource::Inherited(_) => 0,
+        }
+    }
+
+    /// Marker shown after a tag in listings, manual and imported tags aren't marked
+    fn marker(&self) -> &'static str {
+        match self {
+            TagSource::Manual | TagSource::Imported => "",
+            TagSource::Auto(_) => "*",
+            TagSource::Inherited(_) => "^",
+        }
+    }
+}
+
+impl std::fmt::Display for TagSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagSource::Manual => write!(f, "manual"),
+            TagSource::Auto(rule) => write!(f, "auto:{}", rule),
+            TagSource::Inherited(path) => write!(f, "inherited:{}", path),
+            TagSource::Imported => write!(f, "imported"),
+        }
+    }
+}
+
+impl Serialize for TagSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A tag on a project or collection, along with where it came from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TagLink {
+    pub tag: Tag,
+    pub source: TagSource,
+    /// When the link was made, `None` for links older than provenance tracking
+    pub added_at: Option<String>,
+}
+
+/// Sorted tag names followed by the marker of their source, e.g. `git* rust`. A tag that is
+/// linked in multiple ways is shown by its strongest source, see `TagSource::rank`
+fn marked_tags(tags: &HashSet<Tag>, links: &[TagLink]) -> String {
+    let mut sorted_tags = tags.iter().collect::<Vec<&Tag>>();
+    sorted_tags.sort_by(|a, b| a.name.cmp(&b.name));
+    sorted_tags
+        .iter()
+        .map(|tag| {
+            let marker = links
+                .iter()
+                .filter(|link| &link.tag == *tag)
+                .max_by_key(|link| link.source.rank())
+                .map(|link| link.source.marker())
+                .unwrap_or_default();
+            format!("{}{}", tag.name, marker)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Serialize tags as a list of names sorted alphabetically, so the output is stable
+fn sorted_tags<S: Serializer>(tags: &HashSet<Tag>, serializer: S) -> Result<S::Ok, S::Error> {
+    let mut names = tags.iter().map(|tag| &tag.name).collect::<Vec<_>>();
+    names.sort();
+    names.serialize(serializer)
+}
+
+/// Serialize the collection of a project by its path only
+fn collection_path<S: Serializer>(
+    collection: &Option<Collection>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    collection.as_ref().map(|c| &c.path).serialize(serializer)
+}
+
+/// How the folders in a collection are turned into projects
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanSettings {
+    /// How many levels below the collection folder projects are, or the maximum number of levels
+    /// to search when `repos_only` is set
+    pub depth: usize,
+    /// Only add folders that look like a project root, e.g. contain `.git` or `Cargo.toml`,
+    /// without searching inside them
+    pub repos_only: bool,
+    /// Also add folders starting with a `.`
+    pub include_hidden: bool,
+    /// Globs for folders to skip, matched against the folder name and the path relative to the
+    /// collection. Patterns from the config file and `.ffignore` are added when scanning
+    pub ignore: Vec<String>,
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        ScanSettings {
+            depth: 1,
+            repos_only: false,
+            include_hidden: false,
+            ignore: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct Collection {
+    /// Database id, `None` if the collection isn't stored yet
+    pub id: Option<i64>,
     pub path: String,
+    pub scan: ScanSettings,
+    #[serde(serialize_with = "sorted_tags")]
     pub tags: HashSet<Tag>,
+    pub tag_links: Vec<TagLink>,
 }
 
 impl std::fmt::Display for Collection {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let tagsstr = self
-            .tags
-            .iter()
-            .fold(String::new(), |acc, tag| acc + &tag.name + " ");
-        write!(f, "{} | {}", self.path, tagsstr.trim_end())
+        write!(
+            f,
+            "{} | {}",
+            self.path,
+            marked_tags(&self.tags, &self.tag_links)
+        )
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// A git remote, e.g. `origin`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+}
+
+/// Share (0 to 1) of a language in a project, by bytes
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LanguageShare {
+    pub language: String,
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub struct Project {
+    /// Database id, `None` if the project isn't stored yet
+    pub id: Option<i64>,
+    /// Name of the project folder
     pub name: String,
+    /// Name from the project's `.folder_finder.toml`
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub aliases: Vec<String>,
     pub path: String,
+    #[serde(serialize_with = "collection_path")]
     pub collection: Option<Collection>,
+    #[serde(serialize_with = "sorted_tags")]
     pub tags: HashSet<Tag>,
+    pub tag_links: Vec<TagLink>,
+    pub remotes: Vec<Remote>,
+    pub languages: Vec<LanguageShare>,
 }
 
 impl std::fmt::Display for Project {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut sorted_tags = self.tags.iter().collect::<Vec<&Tag>>();
-        sorted_tags.sort_by(|a, b| a.name.cmp(&b.name));
-        let tagsstr = sorted_tags
-            .iter()
-            .fold(String::new(), |acc, tag| acc + &tag.name + " ");
-
         write!(
             f,
             "{:<25} | {}",
-            self.name,
-            tagsstr.trim_end(),
+            self.shown_name(),
+            marked_tags(&self.tags, &self.tag_links),
             // self.path,
         )
     }
 }
 
+impl Project {
+    /// The display name if there is one, otherwise the folder name
+    pub fn shown_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// Number of projects and collections affected by a change to tags
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagChanges {
+    pub projects: usize,
+    pub collections: usize,
+}
+
+/// A tag found by a detector, along with what it was based on, e.g. `Cargo.toml`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    pub tag: Tag,
+    pub evidence: String,
+}
+
+/// Tags added to and removed from a single project or collection
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagDiff {
+    pub added: Vec<Detection>,
+    pub removed: Vec<Tag>,
+}
+
+impl TagDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+// NOTE: Only a handful of these exist at a time, so the size of a project doesn't matter
+#[allow(clippy::large_enum_variant)]
 pub enum DataType {
     Collection(Collection),
     Project(Project),
@@ -71,23 +280,28 @@ impl DataType {
             _ => None,
         }
     }
+    pub fn kind(&self) -> EmptyDataType {
+        match self {
+            DataType::Collection(_) => EmptyDataType::Collection,
+            DataType::Project(_) => EmptyDataType::Project,
+            DataType::Tag(_) => EmptyDataType::Tag,
+        }
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EmptyDataType {
     Collection,
     Project,
     Tag,
 }
 
-#[derive(Debug, Clone)]
-pub struct AlreadyExistsError;
-
-#[derive(Debug, Clone)]
-pub struct DatabaseError;
-
-#[derive(Debug, Clone)]
-pub enum NotFoundError {
-    Collection,
-    Project,
-    Tag,
+impl std::fmt::Display for EmptyDataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EmptyDataType::Collection => write!(f, "collection"),
+            EmptyDataType::Project => write!(f, "project"),
+            EmptyDataType::Tag => write!(f, "tag"),
+        }
+    }
 }